@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusty_mines::{BoardFile, RustMinefield, Solver, SolverOptions};
+
+// Any input has to give a board or an error, never a panic. Valid boards also go through
+// the setup of a solver, which indexes the board with the parsed positions.
+fuzz_target!(|data: &[u8]| {
+    let source = match std::str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+    let BoardFile {
+        mut minefield,
+        revealed,
+        regions,
+    } = match RustMinefield::parse(source) {
+        Ok(board) => board,
+        Err(_) => return,
+    };
+
+    if let Ok(mut solver) =
+        Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)
+    {
+        for region in regions {
+            let _ = solver.add_region(region);
+        }
+        let _ = solver.check_consistency();
+    }
+});
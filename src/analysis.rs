@@ -0,0 +1,289 @@
+//! Win probability of optimal play, by searching the whole game tree.
+//!
+//! The player only sees the revealed cells, so the search works on the set of mine layouts
+//! that agree with them. Revealing a cell splits that set by the number the cell would show,
+//! and the win probability of a position is the best over all cells of the chance to survive
+//! the reveal times the win probability of what it shows. This is exponential in the number
+//! of cells and only feasible on tiny boards; layouts are bitmasks so at most 64 cells.
+
+use crate::{topology::Topology, Cell, Pos, SolverError};
+use std::collections::HashMap;
+
+struct Search {
+    /// Neighbor bitmask of every cell
+    neighbors: Vec<u64>,
+    all: u64,
+    mines: u32,
+    /// Win probability by revealed cells and the layouts that agree with them
+    memo: HashMap<(u64, Vec<u64>), f64>,
+}
+
+impl Search {
+    /// Group the layouts where `cell` is safe by the number it shows
+    fn split(&self, cell: usize, layouts: &[u64]) -> Vec<Vec<u64>> {
+        let mut groups: HashMap<u32, Vec<u64>> = HashMap::new();
+        for layout in layouts.iter().copied() {
+            if layout & (1 << cell) == 0 {
+                let number = (layout & self.neighbors[cell]).count_ones();
+                groups.entry(number).or_default().push(layout);
+            }
+        }
+        let mut groups: Vec<(u32, Vec<u64>)> = groups.into_iter().collect();
+        groups.sort_unstable_by_key(|(number, _)| *number);
+        groups.into_iter().map(|(_, layouts)| layouts).collect()
+    }
+
+    /// Win probability after revealing `cell`, as a fraction of `total` layouts
+    fn reveal(&mut self, revealed: u64, cell: usize, layouts: &[u64], total: usize) -> f64 {
+        self.split(cell, layouts)
+            .into_iter()
+            .map(|group| {
+                let weight = group.len() as f64 / total as f64;
+                weight * self.value(revealed | (1 << cell), group)
+            })
+            .sum()
+    }
+
+    fn value(&mut self, revealed: u64, layouts: Vec<u64>) -> f64 {
+        let hidden = self.all & !revealed;
+        if hidden.count_ones() == self.mines {
+            return 1f64;
+        }
+        if let Some(value) = self.memo.get(&(revealed, layouts.clone())) {
+            return *value;
+        }
+
+        // Revealing a cell that is safe in every layout never hurts, so don't branch
+        let any_mine = layouts.iter().fold(0, |acc, layout| acc | layout);
+        let safe = hidden & !any_mine;
+        let value = if safe != 0 {
+            let cell = safe.trailing_zeros() as usize;
+            self.reveal(revealed, cell, &layouts, layouts.len())
+        } else {
+            cells(hidden)
+                .map(|cell| self.reveal(revealed, cell, &layouts, layouts.len()))
+                .fold(0f64, f64::max)
+        };
+
+        self.memo.insert((revealed, layouts), value);
+        value
+    }
+}
+
+fn cells(mask: u64) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |cell| mask & (1 << cell) != 0)
+}
+
+/// Every layout with `mines` mines on the `hidden` cells, plus the cells in `base`
+fn layouts(hidden: &[usize], mines: u32, base: u64, out: &mut Vec<u64>) {
+    if mines == 0 {
+        out.push(base);
+        return;
+    }
+    if let Some((first, rest)) = hidden.split_first() {
+        if rest.len() >= mines as usize {
+            layouts(rest, mines, base, out);
+        }
+        layouts(rest, mines - 1, base | (1 << first), out);
+    }
+}
+
+/// Search over the layouts that agree with a board
+struct Position {
+    search: Search,
+    revealed: u64,
+    layouts: Vec<u64>,
+}
+
+impl Position {
+    /// Win probability of revealing `cell` and playing optimally after. Before the first
+    /// reveal only the layouts where it is safe count, like the mine placement of
+    /// `RustMinefield`.
+    fn reveal(&mut self, cell: usize) -> f64 {
+        if self.revealed == 0 {
+            let safe: Vec<u64> = self
+                .layouts
+                .iter()
+                .copied()
+                .filter(|layout| layout & (1 << cell) == 0)
+                .collect();
+            return self.search.reveal(0, cell, &safe, safe.len());
+        }
+        self.search
+            .reveal(self.revealed, cell, &self.layouts, self.layouts.len())
+    }
+}
+
+fn check_size(size: usize, cell_limit: usize) -> Result<(), SolverError> {
+    if size > cell_limit.min(64) {
+        return Err(SolverError::TooBigForSearch {
+            cells: size,
+            limit: cell_limit.min(64),
+        });
+    }
+    Ok(())
+}
+
+/// Layouts that agree with `board`, stored row by row, ready to search
+fn position(
+    board: &[Cell],
+    width: i32,
+    height: i32,
+    mines: i32,
+    topology: &dyn Topology,
+) -> Result<Position, SolverError> {
+    let size = board.len();
+    let index = |Pos(col, row): Pos| -> Option<usize> {
+        (col >= 0 && col < width && row >= 0 && row < height)
+            .then(|| usize::try_from(col + row * width).unwrap())
+    };
+    let neighbors: Vec<u64> = (0..height)
+        .flat_map(|row| (0..width).map(move |col| Pos(col, row)))
+        .map(|pos| {
+            topology
+                .neighbors(pos)
+                .into_iter()
+                .filter_map(index)
+                .fold(0, |acc, i| acc | (1 << i))
+        })
+        .collect();
+
+    let mut revealed = 0u64;
+    let mut flags = 0u64;
+    let mut hidden = Vec::new();
+    for (i, cell) in board.iter().enumerate() {
+        match cell {
+            Cell::Number(_) | Cell::Hidden => revealed |= 1 << i,
+            Cell::Flag => flags |= 1 << i,
+            _ => hidden.push(i),
+        }
+    }
+
+    // No layout has a negative number of mines, or more flags than mines
+    let mines = u32::try_from(mines).map_err(|_| SolverError::NoFit)?;
+    let remaining = mines
+        .checked_sub(flags.count_ones())
+        .ok_or(SolverError::NoFit)?;
+    let mut candidates = Vec::new();
+    layouts(&hidden, remaining, flags, &mut candidates);
+    let consistent: Vec<u64> = candidates
+        .into_iter()
+        .filter(|layout| {
+            board.iter().enumerate().all(|(i, cell)| match cell {
+                Cell::Number(n) => (layout & neighbors[i]).count_ones() == u32::from(*n),
+                _ => true,
+            })
+        })
+        .collect();
+    if consistent.is_empty() {
+        return Err(SolverError::NoFit);
+    }
+
+    let search = Search {
+        neighbors,
+        all: if size == 64 {
+            u64::MAX
+        } else {
+            (1 << size) - 1
+        },
+        mines,
+        memo: HashMap::new(),
+    };
+    Ok(Position {
+        search,
+        revealed,
+        layouts: consistent,
+    })
+}
+
+/// Probability that optimal play wins from `board`, stored row by row. Flags count as known
+/// mines. On a board without revealed cells the first reveal is safe, like the mine
+/// placement of `RustMinefield`.
+pub(crate) fn win_probability(
+    board: &[Cell],
+    width: i32,
+    height: i32,
+    mines: i32,
+    topology: &dyn Topology,
+    cell_limit: usize,
+) -> Result<f64, SolverError> {
+    check_size(board.len(), cell_limit)?;
+    if board.contains(&Cell::Mine) {
+        return Ok(0f64);
+    }
+
+    let mut position = position(board, width, height, mines, topology)?;
+    if position.revealed == 0 {
+        let value = (0..board.len())
+            .map(|cell| position.reveal(cell))
+            .fold(0f64, f64::max);
+        return Ok(value);
+    }
+
+    let (revealed, layouts) = (position.revealed, std::mem::take(&mut position.layouts));
+    Ok(position.search.value(revealed, layouts))
+}
+
+/// Win probability of optimal play after revealing each unknown cell of `board`, to rate a
+/// guess against the best one
+pub(crate) fn guess_values(
+    board: &[Cell],
+    width: i32,
+    height: i32,
+    mines: i32,
+    topology: &dyn Topology,
+    cell_limit: usize,
+) -> Result<Vec<(Pos, f64)>, SolverError> {
+    check_size(board.len(), cell_limit)?;
+    if board.contains(&Cell::Mine) {
+        return Err(SolverError::GameOver);
+    }
+
+    let mut position = position(board, width, height, mines, topology)?;
+    let values = board
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| **cell == Cell::Unknown)
+        .map(|(cell, _)| {
+            let i = i32::try_from(cell).unwrap();
+            (Pos(i % width, i / width), position.reveal(cell))
+        })
+        .collect();
+    Ok(values)
+}
+
+#[test]
+fn fifty_fifty() -> anyhow::Result<()> {
+    use crate::topology::Square;
+
+    // Two `1`s over the same two unknowns
+    let board = [
+        Cell::Number(1),
+        Cell::Number(1),
+        Cell::Unknown,
+        Cell::Unknown,
+    ];
+    let p = win_probability(&board, 2, 2, 1, &Square, 16)?;
+    assert!((p - 0.5).abs() < 1e-9);
+
+    // The `0` clears its neighbors, which leaves the corner for the mine
+    let board = [Cell::Unknown, Cell::Unknown, Cell::Number(0), Cell::Unknown];
+    let p = win_probability(&board, 4, 1, 1, &Square, 16)?;
+    assert!((p - 1f64).abs() < 1e-9);
+
+    assert!(win_probability(&board, 4, 1, 1, &Square, 3).is_err());
+
+    // The `1` only touches the middle cell, which makes it the mine and the far end safe
+    let board = [Cell::Unknown, Cell::Unknown, Cell::Number(1)];
+    let values = guess_values(&board, 3, 1, 1, &Square, 16)?;
+    assert_eq!(values, vec![(Pos(0, 0), 1f64), (Pos(1, 0), 0f64)]);
+
+    // Two flags on a board of one mine
+    let board = [Cell::Flag, Cell::Flag, Cell::Unknown];
+    assert!(matches!(
+        win_probability(&board, 3, 1, 1, &Square, 16),
+        Err(SolverError::NoFit)
+    ));
+
+    Ok(())
+}
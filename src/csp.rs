@@ -0,0 +1,264 @@
+//! Exact mine probabilities by enumerating all consistent assignments of the border.
+//!
+//! Every unknown cell next to a revealed number is a variable, every revealed number is a
+//! constraint on the sum of its unknown neighbors. The search backtracks over the variables
+//! and prunes partial assignments that break a constraint or the remaining mine budget.
+//! Cells that don't touch a number share the leftover mines, so each full assignment with
+//! `k` border mines is weighted by the number of ways to place the other mines there.
+
+use crate::{Constraint, Pos};
+use std::collections::HashMap;
+
+/// Exact mine probability of each border cell, plus the probability of the other unknowns
+pub(crate) struct Marginals {
+    pub probs: HashMap<Pos, f32>,
+    pub other: Option<f32>,
+}
+
+struct Search {
+    /// Mines each constraint needs
+    needed: Vec<i32>,
+    /// Constraints each variable is part of
+    var_constraints: Vec<Vec<usize>>,
+    /// Mines assigned so far per constraint
+    placed: Vec<i32>,
+    /// Unassigned variables per constraint
+    open: Vec<i32>,
+    assignment: Vec<bool>,
+    /// Weight of an assignment by its number of border mines
+    weights: Vec<f64>,
+    counts: Vec<f64>,
+    total: f64,
+    other: f64,
+    remaining_mines: i32,
+    isolated: i32,
+    nodes: usize,
+    node_limit: usize,
+}
+
+impl Search {
+    /// Apply or revert an assignment, returns whether all touched constraints can still be met
+    fn update(&mut self, var: usize, mine: bool, revert: bool) -> bool {
+        let mut feasible = true;
+        for c in self.var_constraints[var].iter().copied() {
+            if revert {
+                self.open[c] += 1;
+                self.placed[c] -= i32::from(mine);
+            } else {
+                self.open[c] -= 1;
+                self.placed[c] += i32::from(mine);
+            }
+            feasible &=
+                self.placed[c] <= self.needed[c] && self.placed[c] + self.open[c] >= self.needed[c];
+        }
+        feasible
+    }
+
+    /// Returns false when the node limit was hit
+    fn search(&mut self, var: usize, mines: i32) -> bool {
+        self.nodes += 1;
+        if self.nodes > self.node_limit {
+            return false;
+        }
+
+        let left: i32 = (self.assignment.len() - var).try_into().unwrap();
+        if mines > self.remaining_mines || mines + left + self.isolated < self.remaining_mines {
+            return true;
+        }
+
+        if var == self.assignment.len() {
+            let weight = self.weights[usize::try_from(mines).unwrap()];
+            self.total += weight;
+            self.other += weight * (self.remaining_mines - mines) as f64;
+            for (count, mine) in self.counts.iter_mut().zip(self.assignment.iter()) {
+                if *mine {
+                    *count += weight;
+                }
+            }
+            return true;
+        }
+
+        for mine in [false, true] {
+            self.assignment[var] = mine;
+            let feasible = self.update(var, mine, false);
+            let completed = !feasible || self.search(var + 1, mines + i32::from(mine));
+            self.update(var, mine, true);
+            if !completed {
+                return false;
+            }
+        }
+        self.assignment[var] = false;
+
+        true
+    }
+}
+
+/// Number of ways to choose `k` out of `n`, as float because it gets huge quickly
+fn binomial(n: i32, k: i32) -> f64 {
+    if k < 0 || k > n {
+        return 0f64;
+    }
+    (1..=k.min(n - k)).fold(1f64, |acc, i| acc * f64::from(n - k + i) / f64::from(i))
+}
+
+/// Compute exact marginals for the unknowns of the `constraints`. Returns `None` when the search
+/// exceeds `node_limit` or when no assignment satisfies the constraints.
+pub(crate) fn marginals(
+    constraints: &[Constraint],
+    remaining_mines: i32,
+    unknowns: i32,
+    node_limit: usize,
+) -> Option<Marginals> {
+    // Number the variables in constraint order, so neighboring cells are assigned together
+    let mut variables: Vec<Pos> = Vec::new();
+    let mut index: HashMap<Pos, usize> = HashMap::new();
+    for constraint in constraints {
+        for pos in &constraint.unknowns {
+            index.entry(*pos).or_insert_with(|| {
+                variables.push(*pos);
+                variables.len() - 1
+            });
+        }
+    }
+
+    let mut var_constraints = vec![Vec::new(); variables.len()];
+    for (c, constraint) in constraints.iter().enumerate() {
+        for pos in &constraint.unknowns {
+            var_constraints[index[pos]].push(c);
+        }
+    }
+
+    let isolated = unknowns - i32::try_from(variables.len()).unwrap();
+    let weights = (0..=variables.len())
+        .map(|k| binomial(isolated, remaining_mines - i32::try_from(k).unwrap()))
+        .collect();
+
+    let mut search = Search {
+        needed: constraints.iter().map(|c| c.mines_remaining).collect(),
+        var_constraints,
+        placed: vec![0; constraints.len()],
+        open: constraints
+            .iter()
+            .map(|c| c.unknowns.len().try_into().unwrap())
+            .collect(),
+        assignment: vec![false; variables.len()],
+        weights,
+        counts: vec![0f64; variables.len()],
+        total: 0f64,
+        other: 0f64,
+        remaining_mines,
+        isolated,
+        nodes: 0,
+        node_limit,
+    };
+
+    if !search.search(0, 0) || search.total == 0f64 {
+        return None;
+    }
+
+    let probs = variables
+        .iter()
+        .zip(search.counts.iter())
+        .map(|(pos, count)| (*pos, (count / search.total) as f32))
+        .collect();
+    let other = (isolated > 0).then(|| (search.other / search.total / f64::from(isolated)) as f32);
+
+    Some(Marginals { probs, other })
+}
+
+/// Split the unknowns of the `constraints` into groups that share no constraint, so each can
+/// be solved on its own. Every group is sorted, the biggest group comes first.
+pub(crate) fn components(constraints: &[Constraint]) -> Vec<Vec<Pos>> {
+    // Union-find over the cells, joining the cells of each constraint
+    let mut parent: HashMap<Pos, Pos> = HashMap::new();
+    fn root(parent: &mut HashMap<Pos, Pos>, pos: Pos) -> Pos {
+        let up = *parent.entry(pos).or_insert(pos);
+        if up == pos {
+            return pos;
+        }
+        let root = root(parent, up);
+        parent.insert(pos, root);
+        root
+    }
+    for constraint in constraints {
+        if let Some((first, rest)) = constraint.unknowns.split_first() {
+            let first = root(&mut parent, *first);
+            for pos in rest {
+                let other = root(&mut parent, *pos);
+                parent.insert(other, first);
+            }
+        }
+    }
+
+    let mut groups: HashMap<Pos, Vec<Pos>> = HashMap::new();
+    let cells: Vec<Pos> = parent.keys().copied().collect();
+    for pos in cells {
+        let root = root(&mut parent, pos);
+        groups.entry(root).or_default().push(pos);
+    }
+    let mut groups: Vec<Vec<Pos>> = groups.into_values().collect();
+    for group in &mut groups {
+        group.sort();
+    }
+    groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+    groups
+}
+
+#[test]
+fn fifty_fifty() {
+    // A single `1` touching two unknowns, with no other cells left
+    let constraints = vec![Constraint {
+        unknowns: vec![Pos(0, 0), Pos(1, 0)],
+        mines_remaining: 1,
+    }];
+    let result = marginals(&constraints, 1, 2, 1000).unwrap();
+    assert!((result.probs[&Pos(0, 0)] - 0.5).abs() < 0.0001);
+    assert!((result.probs[&Pos(1, 0)] - 0.5).abs() < 0.0001);
+    assert_eq!(result.other, None);
+}
+
+#[test]
+fn weighted_by_isolated_cells() {
+    // The only mine has to be next to the `1`, so the 3 isolated cells are safe
+    let constraints = vec![
+        Constraint {
+            unknowns: vec![Pos(0, 0)],
+            mines_remaining: 0,
+        },
+        Constraint {
+            unknowns: vec![Pos(1, 0), Pos(2, 0)],
+            mines_remaining: 1,
+        },
+    ];
+    let result = marginals(&constraints, 1, 6, 1000).unwrap();
+    assert_eq!(result.probs[&Pos(0, 0)], 0f32);
+    assert!((result.probs[&Pos(1, 0)] - 0.5).abs() < 0.0001);
+    assert_eq!(result.other, Some(0f32));
+
+    // Node limit hit
+    assert!(marginals(&constraints, 1, 6, 2).is_none());
+}
+
+#[test]
+fn separate_components() {
+    let constraint = |unknowns: &[Pos]| Constraint {
+        unknowns: unknowns.to_vec(),
+        mines_remaining: 1,
+    };
+    // Two chains joined through a shared cell, and a pair on its own
+    let constraints = vec![
+        constraint(&[Pos(5, 5), Pos(6, 5)]),
+        constraint(&[Pos(0, 1), Pos(0, 0)]),
+        constraint(&[Pos(0, 2), Pos(0, 1)]),
+        constraint(&[Pos(1, 2)]),
+    ];
+    assert_eq!(
+        components(&constraints),
+        vec![
+            vec![Pos(0, 0), Pos(0, 1), Pos(0, 2)],
+            vec![Pos(5, 5), Pos(6, 5)],
+            vec![Pos(1, 2)],
+        ]
+    );
+    assert!(components(&[]).is_empty());
+}
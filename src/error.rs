@@ -0,0 +1,77 @@
+use crate::{Cell, Pos};
+use thiserror::Error;
+
+/// Everything that can go wrong while solving, for callers that want to handle the error
+/// instead of printing it
+#[derive(Debug, Error)]
+pub enum SolverError {
+    /// Empty board, or one with more cells than fit in an `i32`
+    #[error("Board of {width}x{height} is not supported")]
+    UnsupportedBoard { width: i32, height: i32 },
+    /// Board with more cells than `SolverOptions::max_board_cells`
+    #[error("Board of {width}x{height} has {cells} cells, more than the limit of {limit}")]
+    BoardTooLarge {
+        width: i32,
+        height: i32,
+        cells: usize,
+        limit: usize,
+    },
+    /// `Compat::Msvc` only reaches `MSVC_MAX_SIDE` columns and rows
+    #[error("Board of {width}x{height} is too big for the MSVC rand(), at most 32768 per side")]
+    MsvcBoardTooLarge { width: i32, height: i32 },
+    #[error("Cell {pos:?} is off the board")]
+    OutOfBounds { pos: Pos },
+    /// The cell is already opened or flagged
+    #[error("Cell {pos:?} is not an unknown cell")]
+    AlreadyKnown { pos: Pos },
+    /// A flag on a cell that was marked safe
+    #[error("Cell {pos:?} is known to be safe, it can't be a mine")]
+    KnownSafe { pos: Pos },
+    /// Only numbers and flags can be revealed
+    #[error("Can't reveal {cell:?}")]
+    CantReveal { cell: Cell },
+    /// A number that doesn't agree with its neighbors, `mines` counts the flagged and
+    /// exploded ones
+    #[error(
+        "Cell {pos:?} shows {number} but has {mines} flagged or exploded and {unknowns} unknown \
+         neighbors"
+    )]
+    Inconsistent {
+        pos: Pos,
+        number: u8,
+        mines: i32,
+        unknowns: i32,
+    },
+    #[error("Region lists cell {pos:?} twice")]
+    RegionCellTwice { pos: Pos },
+    #[error("Region of {cells} cells can't hold {mines} mines")]
+    RegionMines { cells: usize, mines: i32 },
+    #[error("The game is over")]
+    GameOver,
+    /// The deductions got stuck with `SolverOptions::strict_no_guess`, `at` is the cell the
+    /// solver would have guessed
+    #[error("Solving needs a guess, at {at:?}")]
+    GuessRequired { at: Pos },
+    #[error("No unknown cell left to guess")]
+    NothingToGuess,
+    /// The mine probabilities have a cell off the border as best guess, but every unknown cell
+    /// is on the border
+    #[error("No unknown cell off the border to guess")]
+    NoCellOffBorder,
+    /// No layout of the remaining mines agrees with the revealed numbers
+    #[error("No mine layout agrees with the board")]
+    NoFit,
+    #[error("Board of {cells} cells is too big for the optimal search, the limit is {limit}")]
+    TooBigForSearch { cells: usize, limit: usize },
+    /// Only a won game reveals the whole mine layout
+    #[error("The mine layout is only known once the game is won")]
+    LayoutUnknown,
+    /// The board of a won game doesn't make a native minefield
+    #[error("Can't rebuild the mine layout: {reason}")]
+    InvalidLayout { reason: String },
+    #[error("Saved game doesn't match the minefield")]
+    SnapshotMismatch,
+    /// Error of the minefield itself, like a failing call into Python
+    #[error(transparent)]
+    Minefield(#[from] anyhow::Error),
+}
@@ -0,0 +1,119 @@
+//! Arithmetic on the board size, for mine counts like `0.15*w*h + 5`.
+//!
+//! Numbers, `w` and `h` for the width and height, `+ - * /`, unary minus and parentheses,
+//! with the usual precedence.
+
+use anyhow::{anyhow, Result};
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    width: f64,
+    height: f64,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn sum(&mut self) -> Result<f64> {
+        let mut value = self.product()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    value += self.product()?;
+                }
+                Some('-') => {
+                    self.chars.next();
+                    value -= self.product()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64> {
+        let mut value = self.factor()?;
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.chars.next();
+                    value *= self.factor()?;
+                }
+                Some('/') => {
+                    self.chars.next();
+                    value /= self.factor()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(-self.factor()?)
+            }
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(value)
+                    }
+                    _ => Err(anyhow!("Missing `)`")),
+                }
+            }
+            Some('w') => {
+                self.chars.next();
+                Ok(self.width)
+            }
+            Some('h') => {
+                self.chars.next();
+                Ok(self.height)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                number
+                    .parse()
+                    .map_err(|_| anyhow!("Bad number `{}`", number))
+            }
+            Some(c) => Err(anyhow!("Unexpected `{}`", c)),
+            None => Err(anyhow!("Unexpected end")),
+        }
+    }
+}
+
+/// Value of `formula` on a `width` by `height` board
+pub(crate) fn eval(formula: &str, width: i32, height: i32) -> Result<f64> {
+    let mut parser = Parser {
+        chars: formula.chars().peekable(),
+        width: f64::from(width),
+        height: f64::from(height),
+    };
+    let value = parser.sum()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(anyhow!("Unexpected `{}`", c)),
+    }
+}
+
+#[test]
+fn formulas() -> Result<()> {
+    assert_eq!(eval("0.15*w*h + 5", 10, 20)?, 35.0);
+    assert_eq!(eval("-(w - h) / 2", 4, 10)?, 3.0);
+    assert_eq!(eval("2 + 3 * 4", 1, 1)?, 14.0);
+    assert!(eval("w +", 1, 1).is_err());
+    assert!(eval("(w", 1, 1).is_err());
+    assert!(eval("w h", 1, 1).is_err());
+    assert!(eval("1..2", 1, 1).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,4595 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{Subcommand, ValueEnum};
+use owo_colors::OwoColorize;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+// `std::time::Instant` panics on WASM
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+mod analysis;
+mod csp;
+mod error;
+mod formula;
+#[cfg(feature = "python")]
+mod python;
+pub mod stats;
+pub mod topology;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+pub use error::SolverError;
+#[cfg(feature = "python")]
+pub use python::{MinefieldBuilder, PythonMinefield};
+use topology::{Square, Topology};
+
+/// Number of cells on a board. The solver counts cells in `i32`, so bigger boards are an error.
+fn board_size(width: i32, height: i32) -> Result<usize, SolverError> {
+    let size = i64::from(width) * i64::from(height);
+    if width <= 0 || height <= 0 || size > i64::from(i32::MAX) {
+        return Err(SolverError::UnsupportedBoard { width, height });
+    }
+    size.try_into()
+        .map_err(|_| SolverError::UnsupportedBoard { width, height })
+}
+
+/// Default for the biggest board the solver and `RustMinefield` allocate
+pub const DEFAULT_MAX_BOARD_CELLS: usize = 4_000_000;
+
+/// `board_size`, but boards of more than `max_cells` cells are an error instead of a huge
+/// allocation
+fn limited_board_size(width: i32, height: i32, max_cells: usize) -> Result<usize, SolverError> {
+    let size = board_size(width, height)?;
+    if size > max_cells {
+        return Err(SolverError::BoardTooLarge {
+            width,
+            height,
+            cells: size,
+            limit: max_cells,
+        });
+    }
+    Ok(size)
+}
+
+/// Index of `pos` on a board stored row by row, `None` when it is off the board
+fn cell_index(pos: Pos, width: i32, height: i32) -> Option<usize> {
+    let Pos(col, row) = pos;
+    if col < 0 || col >= width || row < 0 || row >= height {
+        return None;
+    }
+    let (col, row, width) = (
+        usize::try_from(col).ok()?,
+        usize::try_from(row).ok()?,
+        usize::try_from(width).ok()?,
+    );
+    row.checked_mul(width)?.checked_add(col)
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Beginner,
+    Intermediate,
+    Expert,
+    /// Named preset from the `--presets` file
+    #[clap(external_subcommand)]
+    Custom(Vec<String>),
+}
+
+impl Mode {
+    /// Board dimensions of the mode, custom modes are resolved against `presets`
+    pub fn preset(&self, presets: &HashMap<String, Preset>) -> Result<Preset> {
+        let (width, height, mines) = match self {
+            Mode::Beginner => (10, 10, 10),
+            Mode::Intermediate => (16, 16, 40),
+            Mode::Expert => (30, 16, 99),
+            Mode::Custom(args) => {
+                let (name, rest) = args
+                    .split_first()
+                    .ok_or_else(|| anyhow!("Missing preset name"))?;
+                if !rest.is_empty() {
+                    return Err(anyhow!("Unexpected arguments after preset '{}'", name));
+                }
+                return presets
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| anyhow!("Unknown preset '{}'", name));
+            }
+        };
+
+        Ok(Preset {
+            width,
+            height,
+            mines,
+        })
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Beginner => write!(f, "beginner"),
+            Mode::Intermediate => write!(f, "intermediate"),
+            Mode::Expert => write!(f, "expert"),
+            Mode::Custom(args) => write!(f, "{}", args.join(" ")),
+        }
+    }
+}
+
+/// Names as on the command line, any other name is a custom preset
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "beginner" => Ok(Mode::Beginner),
+            "intermediate" => Ok(Mode::Intermediate),
+            "expert" => Ok(Mode::Expert),
+            "" => Err(anyhow!("Empty mode name")),
+            name => Ok(Mode::Custom(vec![name.to_string()])),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub width: i32,
+    pub height: i32,
+    pub mines: i32,
+}
+
+impl Preset {
+    /// Validated board dimensions, at least one cell has to stay free for the opening
+    pub fn new(width: i32, height: i32, mines: i32) -> Result<Self> {
+        if width <= 0 || height <= 0 {
+            return Err(anyhow!("Board of {}x{} is empty", width, height));
+        }
+        let size = board_size(width, height)?;
+        if mines < 0 || usize::try_from(mines)? >= size {
+            return Err(anyhow!(
+                "{} mines don't fit on a {}x{} board with a safe opening",
+                mines,
+                width,
+                height
+            ));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            mines,
+        })
+    }
+
+    /// Board with `round(width * height * density)` mines
+    pub fn with_density(width: i32, height: i32, density: f32) -> Result<Self> {
+        if !(density > 0f32 && density < 1f32) {
+            return Err(anyhow!("Density {} is not between 0 and 1", density));
+        }
+        let mines = (width as f32 * height as f32 * density).round() as i32;
+        Self::new(width, height, mines)
+    }
+
+    /// Board with the mine count of `formula`, rounded. The formula can use `w` and `h` for
+    /// the width and height, e.g. `0.15*w*h + 5`.
+    pub fn with_formula(width: i32, height: i32, formula: &str) -> Result<Self> {
+        let value = formula::eval(formula, width, height)
+            .with_context(|| format!("Bad mines formula `{}`", formula))?
+            .round();
+        if !(value >= 0f64 && value <= f64::from(i32::MAX)) {
+            return Err(anyhow!(
+                "Mines formula `{}` gives {} on a {}x{} board, not a mine count",
+                formula,
+                value,
+                width,
+                height
+            ));
+        }
+        Self::new(width, height, value as i32)
+    }
+}
+
+/// Parse named presets from TOML, every table is a preset with `width`, `height` and `mines`
+///
+/// ```toml
+/// [tiny]
+/// width = 5
+/// height = 5
+/// mines = 3
+/// ```
+pub fn load_presets(source: &str) -> Result<HashMap<String, Preset>> {
+    let value: toml::Value = source.parse()?;
+    let table = value
+        .as_table()
+        .ok_or_else(|| anyhow!("Presets must be a table"))?;
+
+    table
+        .iter()
+        .map(|(name, preset)| {
+            if !matches!(name.parse(), Ok(Mode::Custom(_))) {
+                return Err(anyhow!("Preset '{}' has the name of a built-in mode", name));
+            }
+
+            let field = |key: &str| -> Result<i32> {
+                let value = preset
+                    .get(key)
+                    .and_then(toml::Value::as_integer)
+                    .ok_or_else(|| anyhow!("Preset '{}' needs an integer '{}'", name, key))?;
+                Ok(value.try_into()?)
+            };
+
+            let preset = Preset::new(field("width")?, field("height")?, field("mines")?)
+                .map_err(|e| anyhow!("Preset '{}': {}", name, e))?;
+
+            Ok((name.clone(), preset))
+        })
+        .collect()
+}
+
+pub trait Minefield {
+    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell>;
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    fn number_of_mines(&self) -> i32;
+
+    fn topology(&self) -> &'static dyn Topology {
+        &Square
+    }
+
+    /// The whole mine layout, for `Solver::save`. Remote minefields can't provide it.
+    fn saved_layout(&self) -> Result<SavedLayout> {
+        Err(anyhow!("This minefield can't be saved"))
+    }
+
+    /// 3BV of the layout, `None` when the layout is not known
+    fn three_bv(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A sweep seen by a `LoggingMinefield`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoggedSweep {
+    pub pos: Pos,
+    pub cell: Cell,
+    /// Time since the `LoggingMinefield` was created
+    pub at: Duration,
+}
+
+/// Minefield wrapper that logs every sweep, to audit what the solver asked any backend
+pub struct LoggingMinefield<'a, M: Minefield> {
+    minefield: &'a mut M,
+    start: Instant,
+    sweeps: Vec<LoggedSweep>,
+}
+
+impl<'a, M: Minefield> LoggingMinefield<'a, M> {
+    pub fn new(minefield: &'a mut M) -> Self {
+        Self {
+            minefield,
+            start: Instant::now(),
+            sweeps: Vec::new(),
+        }
+    }
+
+    /// Every successful sweep so far, in order
+    pub fn sweeps(&self) -> &[LoggedSweep] {
+        &self.sweeps
+    }
+
+    pub fn into_sweeps(self) -> Vec<LoggedSweep> {
+        self.sweeps
+    }
+}
+
+impl<'a, M: Minefield> Minefield for LoggingMinefield<'a, M> {
+    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell> {
+        let cell = self.minefield.sweep_cell(column, row)?;
+        self.sweeps.push(LoggedSweep {
+            pos: Pos(column, row),
+            cell,
+            at: self.start.elapsed(),
+        });
+        Ok(cell)
+    }
+
+    fn width(&self) -> i32 {
+        self.minefield.width()
+    }
+
+    fn height(&self) -> i32 {
+        self.minefield.height()
+    }
+
+    fn number_of_mines(&self) -> i32 {
+        self.minefield.number_of_mines()
+    }
+
+    fn topology(&self) -> &'static dyn Topology {
+        self.minefield.topology()
+    }
+
+    fn saved_layout(&self) -> Result<SavedLayout> {
+        self.minefield.saved_layout()
+    }
+
+    fn three_bv(&self) -> Option<usize> {
+        self.minefield.three_bv()
+    }
+}
+
+/// Random number generator that places the mines of a `RustMinefield`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MineRng {
+    /// `rand::rngs::StdRng`, its algorithm may change between `rand` versions
+    Std,
+    /// `rand::thread_rng()`, ignores the seed
+    Thread,
+    /// ChaCha20 from `rand_chacha`
+    Chacha,
+    /// PCG XSL RR 128/64 from `rand_pcg`
+    Pcg,
+}
+
+/// How a `RustMinefield` maps its seed to a mine layout. Other than the default, these are
+/// fully specified, so boards of other engines that use them can be reproduced by seed.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compat {
+    /// Rejection sampling with the `MineRng`, see `RustMinefield::place_mines`
+    Rejection,
+    /// Every cell gets the key `splitmix64(seed + index)`, with the index row by row and
+    /// wrapping addition. The mines go on the cells with the lowest keys, skipping the first
+    /// swept cell.
+    Splitmix,
+    /// The Microsoft C runtime `rand()`, seeded with the lower 32 bits of the seed: the state
+    /// steps as `state * 214013 + 2531011` modulo 2^32 and `rand()` returns bits 16 to 30.
+    /// Each mine draws `rand() % width` for the column and then `rand() % height` for the row,
+    /// and draws again when that cell is the first swept cell or already a mine.
+    ///
+    /// `rand()` never reaches beyond `MSVC_MAX_SIDE`, so wider or taller boards are an error.
+    Msvc,
+}
+
+/// Widest and tallest board of `Compat::Msvc`, `rand()` draws 15 bits
+pub const MSVC_MAX_SIDE: i32 = 1 << 15;
+
+/// The SplitMix64 mixing function, as in `java.util.SplittableRandom`
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Seed for a board named by `name`, 64-bit FNV-1a of its UTF-8 bytes. Unlike the hashers of
+/// `std` this is the same on every machine and Rust version, so names can be shared.
+pub fn seed_from_str(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Results of `play` for the games `0..games`, in order, spread over `threads` threads. When
+/// every game only depends on its number, like a game on seed `base + n`, the results don't
+/// depend on the number of threads.
+pub fn parallel_games<R: Send>(
+    games: usize,
+    threads: usize,
+    play: impl Fn(usize) -> R + Sync,
+) -> Vec<R> {
+    let threads = threads.clamp(1, games.max(1));
+    let play = &play;
+    let mut results: Vec<Option<R>> = (0..games).map(|_| None).collect();
+    std::thread::scope(|scope| {
+        // Thread `t` plays every `threads`th game from `t` on
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                scope.spawn(move || {
+                    (thread..games)
+                        .step_by(threads)
+                        .map(|game| (game, play(game)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            let played = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (game, result) in played {
+                results[game] = Some(result);
+            }
+        }
+    });
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+fn seeded<R: SeedableRng>(seed: Option<u64>) -> R {
+    match seed {
+        Some(seed) => R::seed_from_u64(seed),
+        None => R::from_entropy(),
+    }
+}
+
+#[derive(Clone)]
+pub struct RustMinefield {
+    field: Vec<bool>,
+    width: i32,
+    height: i32,
+    number_of_mines: i32,
+    /// Seed for the mine layout, random when not set
+    seed: Option<u64>,
+    rng: MineRng,
+    compat: Compat,
+    topology: &'static dyn Topology,
+    /// Biggest board to place mines on
+    max_cells: usize,
+}
+
+impl RustMinefield {
+    pub fn new(preset: Preset) -> Self {
+        Self {
+            field: Vec::new(),
+            width: preset.width,
+            height: preset.height,
+            number_of_mines: preset.mines,
+            seed: None,
+            rng: MineRng::Std,
+            compat: Compat::Rejection,
+            topology: &Square,
+            max_cells: DEFAULT_MAX_BOARD_CELLS,
+        }
+    }
+
+    /// Minefield that always gets the same layout for the same seed and first sweep
+    pub fn with_seed(preset: Preset, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..Self::new(preset)
+        }
+    }
+
+    /// Minefield with a fixed mine layout, stored row by row
+    pub fn from_layout(width: i32, height: i32, field: Vec<bool>) -> Result<Self> {
+        if board_size(width, height)? != field.len() {
+            return Err(anyhow!(
+                "Layout of {} cells doesn't match a {}x{} board",
+                field.len(),
+                width,
+                height
+            ));
+        }
+        let number_of_mines = field.iter().filter(|mine| **mine).count().try_into()?;
+        Ok(Self {
+            field,
+            width,
+            height,
+            number_of_mines,
+            seed: None,
+            rng: MineRng::Std,
+            compat: Compat::Rejection,
+            topology: &Square,
+            max_cells: DEFAULT_MAX_BOARD_CELLS,
+        })
+    }
+
+    /// Generator for the mine placement, see `place_mines`
+    pub fn with_rng(self, rng: MineRng) -> Self {
+        Self { rng, ..self }
+    }
+
+    /// Seed to layout mapping of another engine
+    pub fn with_compat(self, compat: Compat) -> Self {
+        Self { compat, ..self }
+    }
+
+    /// Use another grid than the classic square one, e.g. `&Hex`
+    pub fn with_topology(self, topology: &'static dyn Topology) -> Self {
+        Self { topology, ..self }
+    }
+
+    /// Refuse to place mines on boards of more than `max_cells` cells
+    pub fn with_max_cells(self, max_cells: usize) -> Self {
+        Self { max_cells, ..self }
+    }
+
+    /// Minefield of a saved game, see `Solver::save`
+    pub fn from_saved(layout: SavedLayout) -> Result<Self> {
+        let preset = Preset::new(layout.width, layout.height, layout.number_of_mines)?;
+        if layout.field.is_empty() {
+            return Ok(Self::new(preset));
+        }
+        if layout.field.len() != board_size(layout.width, layout.height)? {
+            return Err(anyhow!("Saved layout doesn't match its size"));
+        }
+
+        let minefield = Self::from_layout(layout.width, layout.height, layout.field)?;
+        if minefield.number_of_mines != layout.number_of_mines {
+            return Err(anyhow!("Saved layout doesn't match its number of mines"));
+        }
+        Ok(minefield)
+    }
+
+    /// Mine layout row by row, empty until the first cell is swept
+    pub fn layout(&self) -> &[bool] {
+        &self.field
+    }
+
+    /// Place the mines now instead of on the first sweep, keeping `first` free like a first
+    /// sweep there would
+    pub fn generate(&mut self, first: Pos) -> Result<()> {
+        if self.field.is_empty() {
+            let Pos(col, row) = first;
+            self.sweep_cell(col, row)?;
+        }
+        Ok(())
+    }
+
+    /// Place the mines of a fresh layout from the seed, on any cell but the `forbidden`
+    /// ones. Regenerates the layout when the mines were placed already.
+    ///
+    /// The first sweep does this with only the swept cell forbidden, this keeps a bigger area
+    /// free, e.g. a safe region for a test scenario.
+    pub fn generate_with_mask(&mut self, forbidden: &HashSet<Pos>) -> Result<()> {
+        let size = self.layout_size()?;
+        let mut mask = vec![false; size];
+        for pos in forbidden {
+            let i = cell_index(*pos, self.width, self.height)
+                .ok_or_else(|| anyhow!("Forbidden cell {:?} is off the board", pos))?;
+            mask[i] = true;
+        }
+        let free = size - forbidden.len();
+        if free < self.number_of_mines as usize {
+            return Err(anyhow!(
+                "Only {} cells are free for {} mines",
+                free,
+                self.number_of_mines
+            ));
+        }
+        self.lay_mines(&mask);
+        Ok(())
+    }
+
+    /// Whether solving the board runs into a `Solver::fifty_fifty`. Places the mines first if
+    /// needed. Whenever the deductions get stuck this opens the safest cell that is actually
+    /// safe, so the whole board gets checked and not only the part before the first guess.
+    pub fn has_fifty_fifty(&mut self, options: SolverOptions) -> Result<bool> {
+        let first = options
+            .first_guess
+            .pos(self.width, self.height, self.number_of_mines);
+        self.generate(first)?;
+        let mut minefield = self.clone();
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+
+        while solver.outcome.is_none() && !solver.solve_without_guessing()? {
+            if solver.fifty_fifty().is_some() {
+                return Ok(true);
+            }
+            let probabilities = solver.guess_probabilities();
+            let p = |pos: &Pos| {
+                let p = probabilities.border.get(pos).copied();
+                p.or(probabilities.other).unwrap_or(1f32)
+            };
+            let safe = solver
+                .positions()
+                .filter(|pos| {
+                    solver.get(*pos) == Some(Cell::Unknown) && self.get(pos.0, pos.1) == Some(false)
+                })
+                .min_by(|a, b| p(a).partial_cmp(&p(b)).unwrap());
+            match safe {
+                Some(pos) => solver.guess((pos, p(&pos)))?,
+                None => break,
+            };
+        }
+        Ok(false)
+    }
+
+    /// The mine layout as a board file with every cell hidden, see `parse`. Errors when the
+    /// mines are not placed yet.
+    pub fn to_board_file(&self) -> Result<String> {
+        if self.field.is_empty() {
+            return Err(anyhow!("The mines are not placed yet"));
+        }
+
+        let mut board = format!("{} {} {}\n", self.width, self.height, self.number_of_mines);
+        for row in self.field.chunks(usize::try_from(self.width)?) {
+            board.extend(row.iter().map(|mine| if *mine { '*' } else { '.' }));
+            board.push('\n');
+        }
+        Ok(board)
+    }
+
+    /// The placed mines as Python dict literal, with the `MineField` constructor arguments
+    /// of the challenge and the rows of the layout as in a board file
+    pub fn to_python(&self) -> Result<String> {
+        let board = self.to_board_file()?;
+        let mut python = format!(
+            "{{\n    \"width\": {},\n    \"height\": {},\n    \"number_of_mines\": {},\n    \"layout\": [\n",
+            self.width, self.height, self.number_of_mines
+        );
+        for row in board.lines().skip(1) {
+            python.push_str(&format!("        \"{}\",\n", row));
+        }
+        python.push_str("    ],\n}\n");
+        Ok(python)
+    }
+
+    /// Hash of the mine layout that is the same for all rotations and reflections of it, to
+    /// deduplicate generated boards. Non-square boards only have the four symmetries that
+    /// keep their shape. The symmetries are those of the `Square` topology.
+    pub fn canonical_hash(&self) -> u64 {
+        let (width, height) = (self.width, self.height);
+        // Source cell of every target cell, given the width and height
+        type Symmetry = fn(i32, i32, i32, i32) -> (i32, i32);
+        let mut symmetries: Vec<Symmetry> = vec![
+            |col, row, _, _| (col, row),
+            |col, row, width, _| (width - 1 - col, row),
+            |col, row, _, height| (col, height - 1 - row),
+            |col, row, width, height| (width - 1 - col, height - 1 - row),
+        ];
+        if width == height {
+            symmetries.extend([
+                |col, row, _, _| (row, col),
+                |col, row, width, height| (width - 1 - row, height - 1 - col),
+                |col, row, width, _| (row, width - 1 - col),
+                |col, row, _, height| (height - 1 - row, col),
+            ] as [Symmetry; 4]);
+        }
+
+        let canonical = symmetries
+            .iter()
+            .map(|symmetry| {
+                if self.field.is_empty() {
+                    return Vec::new();
+                }
+                (0..height)
+                    .flat_map(|row| (0..width).map(move |col| (col, row)))
+                    .map(|(col, row)| {
+                        let (col, row) = symmetry(col, row, width, height);
+                        self.field[cell_index(Pos(col, row), width, height).unwrap()]
+                    })
+                    .collect::<Vec<bool>>()
+            })
+            .min()
+            .unwrap();
+
+        let mut hasher = DefaultHasher::new();
+        (width, height, canonical).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Parse a board file, returning the minefield, the cells that are already revealed and
+    /// the region hints.
+    ///
+    /// The first line holds `width height mines`, followed by one line per row with a
+    /// character per cell: `.` hidden safe cell, `*` hidden mine, `F` flagged mine, a digit
+    /// for an opened cell showing that number, or `?` for an opened cell with a hidden number.
+    /// After the rows, each line `region MINES COL,ROW ...` adds a hint that the listed cells
+    /// hold `MINES` mines.
+    pub fn parse(source: &str) -> Result<BoardFile> {
+        let mut lines = source.lines();
+        let header: Vec<i32> = lines
+            .next()
+            .ok_or_else(|| anyhow!("Empty board file"))?
+            .split_whitespace()
+            .map(|value| value.parse())
+            .collect::<Result<_, _>>()?;
+        let (width, height, number_of_mines) = match header[..] {
+            [width, height, mines] if width > 0 && height > 0 => (width, height, mines),
+            _ => return Err(anyhow!("Header must be `width height mines`")),
+        };
+
+        let mut field = Vec::new();
+        let mut revealed = Vec::new();
+        for row in 0..height {
+            let line = lines
+                .next()
+                .ok_or_else(|| anyhow!("Missing row {}", row))?
+                .trim_end();
+            if line.chars().count() != width.try_into()? {
+                return Err(anyhow!("Row {} is not {} cells wide", row, width));
+            }
+
+            for (col, c) in (0..).zip(line.chars()) {
+                let pos = Pos(col, row);
+                match c {
+                    '.' => field.push(false),
+                    '*' => field.push(true),
+                    'F' => {
+                        field.push(true);
+                        revealed.push((pos, Cell::Flag));
+                    }
+                    '0'..='8' => {
+                        field.push(false);
+                        revealed.push((pos, Cell::Number(c as u8 - b'0')));
+                    }
+                    '?' => {
+                        field.push(false);
+                        revealed.push((pos, Cell::Hidden));
+                    }
+                    _ => return Err(anyhow!("Unexpected '{}' at {:?}", c, pos)),
+                }
+            }
+        }
+
+        let mut regions = Vec::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let region = Self::parse_region(line)?;
+            let mines = region
+                .cells
+                .iter()
+                .filter(|pos| matches!(cell_index(**pos, width, height), Some(i) if field[i]))
+                .count();
+            if i32::try_from(mines)? != region.mines {
+                return Err(anyhow!(
+                    "Region hint `{}` has {} mines on the board",
+                    line,
+                    mines
+                ));
+            }
+            regions.push(region);
+        }
+
+        let minefield = Self::from_layout(width, height, field)?;
+        if minefield.number_of_mines != number_of_mines {
+            return Err(anyhow!(
+                "Header says {} mines, but the board has {}",
+                number_of_mines,
+                minefield.number_of_mines
+            ));
+        }
+
+        Ok(BoardFile {
+            minefield,
+            revealed,
+            regions,
+        })
+    }
+
+    /// Parse a file of several boards in the format of `parse`. Boards are separated by a
+    /// `---` line, or by a blank line before the header of the next board.
+    pub fn parse_all(source: &str) -> Result<Vec<BoardFile>> {
+        let is_header = |line: &str| {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            values.len() == 3 && values.iter().all(|value| value.parse::<i32>().is_ok())
+        };
+
+        let mut sources = vec![String::new()];
+        let mut after_blank = false;
+        for line in source.lines() {
+            let current = sources.last_mut().unwrap();
+            if line.trim() == "---" {
+                sources.push(String::new());
+            } else if line.trim().is_empty() {
+                after_blank = true;
+                continue;
+            } else if after_blank && is_header(line) && !current.is_empty() {
+                sources.push(format!("{}\n", line));
+            } else {
+                current.push_str(line);
+                current.push('\n');
+            }
+            after_blank = false;
+        }
+        sources.retain(|source| !source.is_empty());
+        if sources.is_empty() {
+            return Err(anyhow!("Empty board file"));
+        }
+
+        sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| Self::parse(source).with_context(|| format!("Board {}", i + 1)))
+            .collect()
+    }
+
+    /// Parse a `region MINES COL,ROW ...` line of a board file
+    fn parse_region(line: &str) -> Result<Region> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("region") {
+            return Err(anyhow!("Expected a region hint, got `{}`", line));
+        }
+        let mines = words
+            .next()
+            .ok_or_else(|| anyhow!("Region hint `{}` misses the mine count", line))?
+            .parse()?;
+        let cells = words
+            .map(|word| -> Result<Pos> {
+                let (col, row) = word
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("Expected `COL,ROW` in region hint, got `{}`", word))?;
+                Ok(Pos(col.parse()?, row.parse()?))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Region { cells, mines })
+    }
+
+    fn get(&mut self, col: i32, row: i32) -> Option<bool> {
+        let index = cell_index(Pos(col, row), self.width, self.height)?;
+
+        if self.field.is_empty() {
+            let size = board_size(self.width, self.height).ok()?;
+            let mut forbidden = vec![false; size];
+            forbidden[index] = true;
+            self.lay_mines(&forbidden);
+        }
+
+        Some(self.field[index])
+    }
+
+    /// Size of the board to lay the mines on, an error for boards the settings can't fill
+    fn layout_size(&self) -> Result<usize, SolverError> {
+        let size = limited_board_size(self.width, self.height, self.max_cells)?;
+        if self.compat == Compat::Msvc
+            && (self.width > MSVC_MAX_SIDE || self.height > MSVC_MAX_SIDE)
+        {
+            return Err(SolverError::MsvcBoardTooLarge {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        Ok(size)
+    }
+
+    /// Replace `field` by a layout without mines on the `forbidden` cells, which leave room
+    /// for all mines
+    fn lay_mines(&mut self, forbidden: &[bool]) {
+        self.field = vec![false; forbidden.len()];
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        match (self.compat, self.rng) {
+            (Compat::Splitmix, _) => self.place_mines_splitmix(seed, forbidden),
+            (Compat::Msvc, _) => self.place_mines_msvc(seed, forbidden),
+            (Compat::Rejection, MineRng::Std) => {
+                self.place_mines(&mut seeded::<StdRng>(self.seed), forbidden)
+            }
+            (Compat::Rejection, MineRng::Thread) => self.place_mines(&mut thread_rng(), forbidden),
+            (Compat::Rejection, MineRng::Chacha) => {
+                self.place_mines(&mut seeded::<ChaCha20Rng>(self.seed), forbidden)
+            }
+            (Compat::Rejection, MineRng::Pcg) => {
+                self.place_mines(&mut seeded::<Pcg64>(self.seed), forbidden)
+            }
+        }
+    }
+
+    /// Place the mines on the empty `field`, never on a `forbidden` cell, which is only the
+    /// first swept one for a normal game.
+    ///
+    /// To reproduce boards of other engines: repeatedly draw `rng.gen_range(0..size)`, an
+    /// index into the cells stored row by row, and put a mine there unless it is the first
+    /// swept cell or already a mine, until all mines are placed.
+    fn place_mines<R: Rng>(&mut self, rng: &mut R, forbidden: &[bool]) {
+        let size = self.field.len();
+        let mut mines_left = self.number_of_mines;
+        while mines_left != 0 {
+            let random_index = rng.gen_range(0..size);
+            if !forbidden[random_index] && !self.field[random_index] {
+                self.field[random_index] = true;
+                mines_left -= 1;
+            }
+        }
+    }
+
+    /// See `Compat::Splitmix`
+    fn place_mines_splitmix(&mut self, seed: u64, forbidden: &[bool]) {
+        let mut keys: Vec<(u64, usize)> = (0..self.field.len())
+            .filter(|i| !forbidden[*i])
+            .map(|i| (splitmix64(seed.wrapping_add(i as u64)), i))
+            .collect();
+        keys.sort_unstable();
+        for (_, i) in keys.into_iter().take(self.number_of_mines as usize) {
+            self.field[i] = true;
+        }
+    }
+
+    /// See `Compat::Msvc`
+    fn place_mines_msvc(&mut self, seed: u64, forbidden: &[bool]) {
+        let mut state = seed as u32;
+        let mut rand = || {
+            state = state.wrapping_mul(214_013).wrapping_add(2_531_011);
+            (state >> 16) & 0x7fff
+        };
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut mines_left = self.number_of_mines;
+        while mines_left != 0 {
+            let col = rand() % width;
+            let row = rand() % height;
+            let i = (row * width + col) as usize;
+            if !forbidden[i] && !self.field[i] {
+                self.field[i] = true;
+                mines_left -= 1;
+            }
+        }
+    }
+
+    fn neighbors(&mut self, col: i32, row: i32) -> u8 {
+        self.topology
+            .neighbors(Pos(col, row))
+            .into_iter()
+            .map(|Pos(col, row)| -> u8 { self.get(col, row).unwrap_or(false).into() })
+            .sum()
+    }
+}
+
+impl Minefield for RustMinefield {
+    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell> {
+        if self.field.is_empty() {
+            self.layout_size()?;
+        }
+        match self.get(column, row) {
+            Some(true) => Ok(Cell::Mine),
+            Some(false) => Ok(Cell::Number(self.neighbors(column, row))),
+            None => Err(anyhow!("Cell {:?} is off the board", Pos(column, row))),
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn number_of_mines(&self) -> i32 {
+        self.number_of_mines
+    }
+
+    fn topology(&self) -> &'static dyn Topology {
+        self.topology
+    }
+
+    fn saved_layout(&self) -> Result<SavedLayout> {
+        Ok(SavedLayout {
+            width: self.width,
+            height: self.height,
+            number_of_mines: self.number_of_mines,
+            field: self.field.clone(),
+        })
+    }
+
+    fn three_bv(&self) -> Option<usize> {
+        if self.field.is_empty() {
+            return None;
+        }
+
+        let neighbors = |i: usize| -> Vec<usize> {
+            let width = usize::try_from(self.width).unwrap();
+            let pos = Pos((i % width) as i32, (i / width) as i32);
+            self.topology
+                .neighbors(pos)
+                .into_iter()
+                .filter_map(|pos| cell_index(pos, self.width, self.height))
+                .collect()
+        };
+        let zero: Vec<bool> = (0..self.field.len())
+            .map(|i| !self.field[i] && neighbors(i).into_iter().all(|n| !self.field[n]))
+            .collect();
+
+        // One click per opening, the flood fill also opens the numbers around it
+        let mut opened = vec![false; self.field.len()];
+        let mut clicks = 0;
+        for start in 0..self.field.len() {
+            if !zero[start] || opened[start] {
+                continue;
+            }
+            clicks += 1;
+            opened[start] = true;
+            let mut flood = vec![start];
+            while let Some(i) = flood.pop() {
+                for n in neighbors(i) {
+                    if !opened[n] {
+                        opened[n] = true;
+                        if zero[n] {
+                            flood.push(n);
+                        }
+                    }
+                }
+            }
+        }
+
+        // And one for every other safe cell
+        let rest = (0..self.field.len())
+            .filter(|i| !self.field[*i] && !opened[*i])
+            .count();
+        Some(clicks + rest)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pos(pub i32, pub i32);
+
+/// Row by row, like the board is printed
+impl Ord for Pos {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (Pos(col, row), Pos(other_col, other_row)) = (self, other);
+        (row, col).cmp(&(other_row, other_col))
+    }
+}
+
+impl PartialOrd for Pos {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Cell {
+    Unknown,
+    Flag,
+    Number(u8),
+    Mine,
+    /// Opened and safe, but its number is hidden, like the `?` cells of a fog variant or a
+    /// corrupted input. Says nothing about its neighbors.
+    Hidden,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Status {
+    Won,
+    /// Uncovered the mine `at`
+    Exploded {
+        at: Pos,
+    },
+    /// Stopped without uncovering a mine, but also without solving the board
+    Incomplete,
+}
+
+impl Status {
+    /// Name of the variant, without the details
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Won => "Won",
+            Status::Exploded { .. } => "Exploded",
+            Status::Incomplete => "Incomplete",
+        }
+    }
+}
+
+/// What a player sees of a board, for saving and sending it.
+///
+/// Serializes as its size and one string per row with a character per cell, like a board
+/// file: `.` unknown, `F` flag, `*` exploded mine, `?` hidden number, or the digit of an
+/// opened cell.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "BoardRows", try_from = "BoardRows")]
+pub struct BoardState {
+    pub width: i32,
+    pub height: i32,
+    /// Row by row
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BoardRows {
+    width: i32,
+    height: i32,
+    rows: Vec<String>,
+}
+
+impl From<BoardState> for BoardRows {
+    fn from(state: BoardState) -> Self {
+        let rows = state
+            .cells
+            .chunks(usize::try_from(state.width).unwrap_or(0).max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Cell::Unknown => '.',
+                        Cell::Flag => 'F',
+                        Cell::Mine => '*',
+                        Cell::Number(x) => char::from(b'0' + x),
+                        Cell::Hidden => '?',
+                    })
+                    .collect()
+            })
+            .collect();
+        BoardRows {
+            width: state.width,
+            height: state.height,
+            rows,
+        }
+    }
+}
+
+impl TryFrom<BoardRows> for BoardState {
+    type Error = anyhow::Error;
+
+    fn try_from(rows: BoardRows) -> Result<Self> {
+        if usize::try_from(rows.height) != Ok(rows.rows.len()) {
+            return Err(anyhow!("Expected {} rows", rows.height));
+        }
+        let mut cells = Vec::new();
+        for (row, line) in rows.rows.iter().enumerate() {
+            if usize::try_from(rows.width) != Ok(line.chars().count()) {
+                return Err(anyhow!("Row {} is not {} cells wide", row, rows.width));
+            }
+            for c in line.chars() {
+                cells.push(match c {
+                    '.' => Cell::Unknown,
+                    'F' => Cell::Flag,
+                    '*' => Cell::Mine,
+                    '0'..='8' => Cell::Number(c as u8 - b'0'),
+                    '?' => Cell::Hidden,
+                    _ => return Err(anyhow!("Unexpected `{}` in row {}", c, row)),
+                });
+            }
+        }
+        Ok(BoardState {
+            width: rows.width,
+            height: rows.height,
+            cells,
+        })
+    }
+}
+
+impl BoardState {
+    /// The opened and flagged cells, to continue with `Solver::with_revealed`
+    pub fn revealed(&self) -> Vec<(Pos, Cell)> {
+        (0..self.height)
+            .flat_map(|row| (0..self.width).map(move |col| Pos(col, row)))
+            .zip(self.cells.iter().copied())
+            .filter(|(_, cell)| *cell != Cell::Unknown)
+            .collect()
+    }
+}
+
+/// Contents of a board file, see `RustMinefield::parse`
+pub struct BoardFile {
+    pub minefield: RustMinefield,
+    /// Cells that are already opened or flagged
+    pub revealed: Vec<(Pos, Cell)>,
+    pub regions: Vec<Region>,
+}
+
+/// Hint of a puzzle variant that there are `mines` mines among `cells`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub cells: Vec<Pos>,
+    pub mines: i32,
+}
+
+/// What a revealed number or region hint still says about the board: there are
+/// `mines_remaining` mines among the `unknowns`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constraint {
+    pub unknowns: Vec<Pos>,
+    pub mines_remaining: i32,
+}
+
+impl Constraint {
+    /// `Some(false)` when all unknowns are safe, `Some(true)` when they are all mines
+    pub fn decided(&self) -> Option<bool> {
+        if self.mines_remaining == 0 {
+            Some(false)
+        } else if usize::try_from(self.mines_remaining) == Ok(self.unknowns.len()) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} mines among", self.mines_remaining)?;
+        for Pos(col, row) in &self.unknowns {
+            write!(f, " ({},{})", col, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the solver opened or flagged a cell
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Reason {
+    /// The first cell of the game
+    Opening,
+    /// The cell was known to be safe before the game, see `Solver::mark_safe`
+    KnownSafe,
+    /// Number `by` already had all its mines flagged
+    Satisfied { by: Pos },
+    /// Number `by` needed all its remaining unknown neighbors as mines
+    OnlyMines { by: Pos },
+    /// All mines of the board are flagged
+    AllMinesFlagged,
+    /// There are as many unknown cells left as mines
+    OnlyMinesLeft,
+    /// Region hint number `region` was left with only safe cells or only mines
+    Region { region: usize },
+    /// No cell was certain, this one had mine probability `p`
+    Guess { p: f32 },
+    /// Move of a human player
+    Player,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Opening => write!(f, "it is the first move"),
+            Reason::KnownSafe => write!(f, "it was known to be safe"),
+            Reason::Satisfied { by: Pos(col, row) } => {
+                write!(f, "cell ({},{}) had all mines flagged", col, row)
+            }
+            Reason::OnlyMines { by: Pos(col, row) } => {
+                write!(f, "cell ({},{}) needed all remaining neighbors", col, row)
+            }
+            Reason::AllMinesFlagged => write!(f, "all mines are flagged"),
+            Reason::OnlyMinesLeft => write!(f, "only mines are left"),
+            Reason::Region { region } => write!(f, "region hint {} left no choice", region),
+            Reason::Guess { p } => write!(f, "it was the best guess, with mine chance {}", p),
+            Reason::Player => write!(f, "the player chose it"),
+        }
+    }
+}
+
+/// A cell the solver opened or flagged
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    pub pos: Pos,
+    /// What the cell turned out to be, `Cell::Flag` for a flagged cell
+    pub cell: Cell,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Pos(col, row) = self.pos;
+        match self.cell {
+            Cell::Flag => write!(f, "flagged ({},{}) because {}", col, row, self.reason),
+            Cell::Mine => write!(f, "hit a mine at ({},{}) because {}", col, row, self.reason),
+            _ => write!(f, "opened ({},{}) because {}", col, row, self.reason),
+        }
+    }
+}
+
+/// What the solver just did, passed to the observer of `Solver::solve_observed`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolveEvent {
+    /// Opened a cell it knew to be safe
+    Uncover { pos: Pos, cell: Cell },
+    /// Flagged a cell it knew to be a mine
+    Flag { pos: Pos },
+    /// Opened a cell with mine probability `p`, `cell` is `Cell::Mine` when it exploded
+    Guess { pos: Pos, cell: Cell, p: f32 },
+}
+
+impl From<&Move> for SolveEvent {
+    fn from(m: &Move) -> Self {
+        match (m.cell, m.reason) {
+            (Cell::Flag, _) => SolveEvent::Flag { pos: m.pos },
+            (cell, Reason::Guess { p }) => SolveEvent::Guess {
+                pos: m.pos,
+                cell,
+                p,
+            },
+            (cell, _) => SolveEvent::Uncover { pos: m.pos, cell },
+        }
+    }
+}
+
+/// Move the solver would make next, from `Solver::suggest`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Suggestion {
+    /// Open a cell that is certainly safe
+    Safe { pos: Pos, reason: Reason },
+    /// Flag a cell that is certainly a mine
+    Mine { pos: Pos, reason: Reason },
+    /// Nothing is certain, open the cell picked by the guess strategy
+    Guess { pos: Pos, p: f32 },
+}
+
+impl fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Suggestion::Safe {
+                pos: Pos(col, row),
+                reason,
+            } => write!(f, "open ({},{}) because {}", col, row, reason),
+            Suggestion::Mine {
+                pos: Pos(col, row),
+                reason,
+            } => write!(f, "flag ({},{}) because {}", col, row, reason),
+            Suggestion::Guess {
+                pos: Pos(col, row),
+                p,
+            } => write!(f, "guess ({},{}), mine chance {:.3}", col, row, p),
+        }
+    }
+}
+
+/// Sum of the probabilities in the same order every time, float addition isn't associative
+fn ordered_sum(probs: &HashMap<Pos, f32>) -> f32 {
+    let mut probs: Vec<(&Pos, &f32)> = probs.iter().collect();
+    probs.sort_by_key(|(pos, _)| **pos);
+    probs.into_iter().map(|(_, p)| p).sum()
+}
+
+/// Most iterations of `relax`
+pub const RELAX_ITERATIONS: usize = 100;
+
+/// Approximate mine probabilities of the `unknowns` of the `constraints`, by iteratively
+/// correcting each constraint towards its mine count. `unknowns` counts all unknown cells of
+/// the board, `remaining_mines` the mines among them. Also returns the iterations it took to
+/// converge, `RELAX_ITERATIONS` when it didn't.
+fn relax(
+    constraints: &[Constraint],
+    remaining_mines: i32,
+    unknowns: i32,
+) -> (HashMap<Pos, f32>, usize) {
+    let naive_chance = remaining_mines as f32 / unknowns as f32;
+
+    let mut probs: HashMap<Pos, f32> = HashMap::new();
+    for constraint in constraints {
+        probs.extend(constraint.unknowns.iter().map(|pos| (*pos, naive_chance)));
+    }
+
+    let mut iterations = 0;
+    while iterations < RELAX_ITERATIONS {
+        iterations += 1;
+        let mut max_correction_diff = 0f32;
+
+        for constraint in constraints {
+            let expected = constraint.mines_remaining as f32;
+            let sum: f32 = constraint.unknowns.iter().map(|pos| probs[pos]).sum();
+            let correction = (expected - sum) / constraint.unknowns.len() as f32;
+
+            max_correction_diff = f32::max(max_correction_diff, f32::abs(correction));
+
+            for pos in &constraint.unknowns {
+                if let Some(p) = probs.get_mut(pos) {
+                    *p = f32::clamp(*p + correction, 0f32, 1f32);
+                }
+            }
+        }
+
+        // Reduce total probability if it is more then the remaining mines
+        let sum = ordered_sum(&probs);
+        if sum > remaining_mines as f32 {
+            let correction = (remaining_mines as f32 - sum) / probs.len() as f32;
+            for p in probs.values_mut() {
+                *p = f32::clamp(*p + correction, 0f32, 1f32);
+            }
+            max_correction_diff = f32::max(max_correction_diff, f32::abs(correction));
+        }
+
+        // Enough conversion, done iterating
+        if max_correction_diff < 0.0001 {
+            break;
+        }
+    }
+
+    (probs, iterations)
+}
+
+/// Whether assuming `safe` has no mine contradicts the `constraints`, found by repeatedly
+/// deciding the constraints that are left with only safe cells or only mines. This catches
+/// what one step of deductions would run into, not every contradiction.
+fn contradicts_safe(constraints: &[Constraint], remaining_mines: i32, safe: Pos) -> bool {
+    let mut known = HashMap::from([(safe, false)]);
+    loop {
+        let mut changed = false;
+        for constraint in constraints {
+            let mines = constraint
+                .unknowns
+                .iter()
+                .filter(|pos| known.get(pos) == Some(&true))
+                .count();
+            let open: Vec<Pos> = constraint
+                .unknowns
+                .iter()
+                .copied()
+                .filter(|pos| !known.contains_key(pos))
+                .collect();
+            let needed = constraint.mines_remaining - mines as i32;
+            if needed < 0 || needed > open.len() as i32 {
+                return true;
+            }
+            if !open.is_empty() && (needed == 0 || needed == open.len() as i32) {
+                known.extend(open.into_iter().map(|pos| (pos, needed > 0)));
+                changed = true;
+            }
+        }
+
+        if known.values().filter(|mine| **mine).count() as i32 > remaining_mines {
+            return true;
+        }
+        if !changed {
+            return false;
+        }
+    }
+}
+
+/// Mine probabilities to pick a guess from
+struct GuessProbabilities {
+    /// Unknown cells that are part of a constraint
+    border: HashMap<Pos, f32>,
+    /// Probability of the other unknowns, if there are any
+    other: Option<f32>,
+    /// Iterations of the relaxation, `None` when the probabilities are exact
+    relax_iterations: Option<usize>,
+}
+
+/// Result of a solve
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub status: Status,
+    /// Chance that all guesses made were safe
+    pub luck: f32,
+    /// Natural log of `luck`, stays meaningful when `luck` underflows on hard boards
+    pub log_luck: f64,
+}
+
+/// Read-only summary of the board a `Solver` is working on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardInfo {
+    pub width: i32,
+    pub height: i32,
+    pub number_of_mines: i32,
+    pub flags_placed: i32,
+    /// `number_of_mines - flags_placed`, like the counter of a classic minesweeper
+    pub mines_remaining: i32,
+    pub unknowns: i32,
+}
+
+impl BoardInfo {
+    /// Every cell uncovered but the flags don't match the mines, so some flag is wrong. Only
+    /// meaningful when the game didn't explode.
+    pub fn flag_mismatch(&self) -> bool {
+        self.unknowns == 0 && self.flags_placed != self.number_of_mines
+    }
+}
+
+/// Mine layout of a `RustMinefield` in a saved game, the field is empty when the mines are
+/// not placed yet
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedLayout {
+    pub width: i32,
+    pub height: i32,
+    pub number_of_mines: i32,
+    pub field: Vec<bool>,
+}
+
+/// A game saved to resume later, see `Solver::save`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SolverSnapshot {
+    pub layout: SavedLayout,
+    pub state: SolverState,
+}
+
+/// Saved progress of a `Solver`, see `Solver::snapshot`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SolverState {
+    board: Vec<Cell>,
+    flags: i32,
+    unknowns: i32,
+    next: Vec<Pos>,
+    settled: Vec<bool>,
+    moves: Vec<Move>,
+    sweeps: Vec<u8>,
+    luck: f32,
+    log_luck: f64,
+    outcome: Option<Outcome>,
+    #[serde(default)]
+    regions: Vec<Region>,
+    #[serde(default)]
+    known_safe: Vec<Pos>,
+    #[serde(default)]
+    flag_clicks: usize,
+}
+
+/// How to pick a cell when the deductions are stuck
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuessStrategy {
+    /// Lowest mine probability
+    Safest,
+    /// Highest `(1 - p) * expected new constraints`, prefers safe cells likely to open up
+    Progress,
+    /// Lowest mine probability, ties go to the cell next to the lowest revealed number
+    Cascade,
+    /// Among the cells within `COMPARABLE_SAFETY` of the lowest mine probability, the one in
+    /// the biggest independent group of the border, see `Solver::border_components`
+    BiggestRegion,
+    /// Any unknown cell, uniformly at random from `SolverOptions::guess_seed`, as baseline for
+    /// what the mine probabilities are worth
+    Random,
+}
+
+/// How much riskier than the safest cell a guess of `GuessStrategy::BiggestRegion` may be
+pub const COMPARABLE_SAFETY: f32 = 0.02;
+
+/// Order of the deductions within a pass over the numbers that changed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeduceOrder {
+    /// Each number as it comes
+    Mixed,
+    /// First every number that opens cells, then the ones that flag
+    UncoverFirst,
+    /// First every number that flags cells, so the numbers after it see the flags
+    FlagFirst,
+}
+
+/// Which of the flags the solver plants cost a click, see `Solver::flag_clicks`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flagging {
+    /// Every deduced mine
+    All,
+    /// Only the mines that complete a number with other unknown neighbors left, so the flag
+    /// lets that number open them. The others are still flag moves, but a player would skip
+    /// them.
+    LoadBearing,
+}
+
+/// Order of the cells in the final reveal, when every mine is flagged or every cell left is
+/// a mine
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepOrder {
+    /// Column by column, top to bottom
+    ColumnMajor,
+    /// Row by row, left to right
+    RowMajor,
+    /// Clockwise along the edges from the top left corner, inwards
+    Spiral,
+}
+
+impl SweepOrder {
+    /// Every cell of a `width` by `height` board in this order
+    pub fn positions(self, width: i32, height: i32) -> Vec<Pos> {
+        match self {
+            SweepOrder::ColumnMajor => (0..width)
+                .flat_map(|col| (0..height).map(move |row| Pos(col, row)))
+                .collect(),
+            SweepOrder::RowMajor => (0..height)
+                .flat_map(|row| (0..width).map(move |col| Pos(col, row)))
+                .collect(),
+            SweepOrder::Spiral => {
+                let mut positions = Vec::new();
+                let (mut left, mut top, mut right, mut bottom) = (0, 0, width - 1, height - 1);
+                while left <= right && top <= bottom {
+                    positions.extend((left..=right).map(|col| Pos(col, top)));
+                    positions.extend((top + 1..=bottom).map(|row| Pos(right, row)));
+                    if top < bottom {
+                        positions.extend((left..right).rev().map(|col| Pos(col, bottom)));
+                    }
+                    if left < right {
+                        positions.extend((top + 1..bottom).rev().map(|row| Pos(left, row)));
+                    }
+                    left += 1;
+                    top += 1;
+                    right -= 1;
+                    bottom -= 1;
+                }
+                positions
+            }
+        }
+    }
+}
+
+/// Where to sweep the first cell of a game
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FirstGuess {
+    /// Top left corner
+    Corner,
+    /// Middle of the top edge
+    Edge,
+    /// Middle of the board
+    Center,
+    /// The best of the above for the board, see `FirstGuess::auto`
+    Auto,
+}
+
+/// Win rate in percent of the corner, edge and center openings per built-in mode board.
+///
+/// Measured on 2000 seeded `RustMinefield` games per mode and opening (seeds 0 to 1999) with
+/// the default solver options. The corner wins everywhere: it has the fewest neighbors, so
+/// it is most likely to be a `0` that opens up an area.
+const OPENING_WIN_RATES: [((i32, i32, i32), [f64; 3]); 3] = [
+    ((10, 10, 10), [94.5, 93.7, 92.1]),
+    ((16, 16, 40), [78.0, 74.0, 69.7]),
+    ((30, 16, 99), [40.1, 37.0, 35.6]),
+];
+
+impl FirstGuess {
+    /// Opening with the best win rate in `OPENING_WIN_RATES`, corner for other boards
+    pub fn auto(width: i32, height: i32, mines: i32) -> Self {
+        let rates = OPENING_WIN_RATES
+            .iter()
+            .find(|(board, _)| *board == (width, height, mines));
+        match rates {
+            Some((_, rates)) => [FirstGuess::Corner, FirstGuess::Edge, FirstGuess::Center]
+                .into_iter()
+                .zip(rates)
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(FirstGuess::Corner, |(first, _)| first),
+            None => FirstGuess::Corner,
+        }
+    }
+
+    /// Cell to sweep first on a board of `width` by `height`
+    pub fn pos(self, width: i32, height: i32, mines: i32) -> Pos {
+        match self {
+            FirstGuess::Corner => Pos(0, 0),
+            FirstGuess::Edge => Pos(width / 2, 0),
+            FirstGuess::Center => Pos(width / 2, height / 2),
+            FirstGuess::Auto => Self::auto(width, height, mines).pos(width, height, mines),
+        }
+    }
+}
+
+/// How `Solver::render` draws the board
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Render {
+    /// A character per cell
+    Plain,
+    /// Like plain, with colored flags and mines
+    Color,
+    /// Inside a box-drawing border, with the coordinates along the edges
+    Unicode,
+    /// Like color, with unknown cells from green to red by their approximate mine probability
+    Heat,
+}
+
+/// Characters `Solver::render` draws the cells with, the numbers are always digits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderStyle {
+    pub flag: String,
+    pub unknown: String,
+    pub mine: String,
+    /// A revealed cell without mines around it
+    pub empty: String,
+    /// The glyphs take two columns, numbers are padded to line up with them
+    pub wide: bool,
+}
+
+impl Default for RenderStyle {
+    fn default() -> Self {
+        RenderStyle {
+            flag: "F".to_string(),
+            unknown: ".".to_string(),
+            mine: "X".to_string(),
+            empty: " ".to_string(),
+            wide: false,
+        }
+    }
+}
+
+impl RenderStyle {
+    /// Glyph of `cell`, without colors
+    pub fn glyph(&self, cell: Cell) -> String {
+        match cell {
+            Cell::Flag => self.flag.clone(),
+            Cell::Unknown => self.unknown.clone(),
+            Cell::Mine => self.mine.clone(),
+            Cell::Number(0) => self.empty.clone(),
+            Cell::Number(x) if self.wide => format!("{} ", x),
+            Cell::Number(x) => x.to_string(),
+            Cell::Hidden if self.wide => "? ".to_string(),
+            Cell::Hidden => "?".to_string(),
+        }
+    }
+
+    /// Columns a cell takes, without the space after it
+    pub fn cell_width(&self) -> usize {
+        if self.wide {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+/// Presets of `RenderStyle`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Glyphs {
+    /// `F` flags, `X` mines and `.` for unknown cells
+    Ascii,
+    /// Flag and bomb emoji, for terminals that draw them two columns wide
+    Emoji,
+}
+
+impl Glyphs {
+    pub fn style(self) -> RenderStyle {
+        match self {
+            Glyphs::Ascii => RenderStyle::default(),
+            Glyphs::Emoji => RenderStyle {
+                flag: "🚩".to_string(),
+                unknown: "⬜".to_string(),
+                mine: "💣".to_string(),
+                empty: "  ".to_string(),
+                wide: true,
+            },
+        }
+    }
+}
+
+/// Time a `Solver` spent so far
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    /// Passes over the active cells
+    pub deduction: Duration,
+    /// Computing probabilities and picking a cell to guess
+    pub guessing: Duration,
+}
+
+/// Tuning knobs of the solver
+#[derive(Clone, Copy, Debug)]
+pub struct SolverOptions {
+    /// Search nodes the exact solver may visit before falling back to the approximation,
+    /// 0 disables the exact solver
+    pub csp_node_limit: usize,
+    pub guess_strategy: GuessStrategy,
+    pub first_guess: FirstGuess,
+    /// Biggest board to solve, bigger ones are an error instead of a huge allocation
+    pub max_board_cells: usize,
+    /// Before each guess, check with the exact solver whether some border cells were certain
+    /// after all, see `Solver::missed_deductions`
+    pub track_missed_deductions: bool,
+    /// Skip guesses that contradict the numbers once assumed safe, which the approximate
+    /// probabilities can miss
+    pub lookahead: bool,
+    /// Stop with `Status::Incomplete` instead of guessing once the deductions are stuck
+    pub logic_only: bool,
+    /// Fail with `SolverError::GuessRequired` instead of guessing a cell that isn't certainly
+    /// safe, for pipelines that only accept boards that need no luck
+    pub strict_no_guess: bool,
+    /// Stop with `Status::Incomplete` once this many cells are opened or flagged
+    pub move_budget: Option<usize>,
+    pub deduce_order: DeduceOrder,
+    pub sweep_order: SweepOrder,
+    pub flagging: Flagging,
+    /// Seed of the guesses of `GuessStrategy::Random`, each guess draws from this seed plus
+    /// the number of moves so far
+    pub guess_seed: u64,
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        Self {
+            csp_node_limit: 100_000,
+            guess_strategy: GuessStrategy::Safest,
+            first_guess: FirstGuess::Corner,
+            max_board_cells: DEFAULT_MAX_BOARD_CELLS,
+            track_missed_deductions: false,
+            lookahead: false,
+            logic_only: false,
+            strict_no_guess: false,
+            move_budget: None,
+            deduce_order: DeduceOrder::Mixed,
+            sweep_order: SweepOrder::ColumnMajor,
+            flagging: Flagging::All,
+            guess_seed: 0,
+        }
+    }
+}
+
+pub struct Solver<'a, T: Minefield> {
+    minefield: &'a mut T,
+    options: SolverOptions,
+    board: Vec<Cell>,
+    flags: i32,
+    unknowns: i32,
+    next: Vec<Pos>,
+    /// Number cells without unknown neighbors, these never change again
+    settled: Vec<bool>,
+    changes: Vec<(Pos, Cell)>,
+    moves: Vec<Move>,
+    /// Times each cell was swept, which should be at most once
+    sweeps: Vec<u8>,
+    luck: f32,
+    log_luck: f64,
+    outcome: Option<Outcome>,
+    timings: Timings,
+    relax_iterations: Vec<usize>,
+    missed: Vec<Pos>,
+    /// Region hints, on top of the revealed numbers
+    regions: Vec<Region>,
+    /// Cells known to be safe before they were opened
+    known_safe: Vec<Pos>,
+    /// Flag moves that cost a click, see `SolverOptions::flagging`
+    flag_clicks: usize,
+}
+
+impl<'a, T: Minefield> Solver<'a, T> {
+    pub fn new(minefield: &'a mut T) -> Result<Self, SolverError> {
+        Self::with_options(minefield, SolverOptions::default())
+    }
+
+    pub fn with_options(minefield: &'a mut T, options: SolverOptions) -> Result<Self, SolverError> {
+        let size = limited_board_size(
+            minefield.width(),
+            minefield.height(),
+            options.max_board_cells,
+        )?;
+        let first = options.first_guess.pos(
+            minefield.width(),
+            minefield.height(),
+            minefield.number_of_mines(),
+        );
+        Ok(Self {
+            minefield,
+            options,
+            board: vec![Cell::Unknown; size],
+            flags: 0,
+            // `board_size` only allows boards that count in an `i32`
+            unknowns: size.try_into().unwrap(),
+            next: vec![first],
+            settled: vec![false; size],
+            changes: Vec::new(),
+            moves: Vec::new(),
+            sweeps: vec![0; size],
+            luck: 1f32,
+            log_luck: 0f64,
+            outcome: None,
+            timings: Timings::default(),
+            relax_iterations: Vec::new(),
+            missed: Vec::new(),
+            regions: Vec::new(),
+            known_safe: Vec::new(),
+            flag_clicks: 0,
+        })
+    }
+
+    /// Start from a board that is already partly opened, e.g. by a server that dictates the
+    /// opening. Deduction starts from the revealed numbers instead of sweeping the first guess.
+    pub fn with_revealed(
+        minefield: &'a mut T,
+        options: SolverOptions,
+        revealed: &[(Pos, Cell)],
+    ) -> Result<Self, SolverError> {
+        let mut solver = Self::with_options(minefield, options)?;
+        for (pos, cell) in revealed.iter().copied() {
+            solver.reveal(pos, cell)?;
+        }
+        Ok(solver)
+    }
+
+    /// Solver for a game where the cells of `safe` are known to be safe, see `mark_safe`
+    pub fn with_known_safe(
+        minefield: &'a mut T,
+        options: SolverOptions,
+        safe: &[Pos],
+    ) -> Result<Self, SolverError> {
+        let mut solver = Self::with_options(minefield, options)?;
+        for pos in safe.iter().copied() {
+            solver.mark_safe(pos)?;
+        }
+        Ok(solver)
+    }
+
+    pub fn info(&self) -> BoardInfo {
+        BoardInfo {
+            width: self.minefield.width(),
+            height: self.minefield.height(),
+            number_of_mines: self.minefield.number_of_mines(),
+            flags_placed: self.flags,
+            mines_remaining: self.mines_remaining(),
+            unknowns: self.unknowns,
+        }
+    }
+
+    /// Every position of the board, row by row like the `Ord` of `Pos`
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        let (width, height) = (self.minefield.width(), self.minefield.height());
+        (0..height).flat_map(move |row| (0..width).map(move |col| Pos(col, row)))
+    }
+
+    /// The board as the player sees it
+    pub fn board_state(&self) -> BoardState {
+        BoardState {
+            width: self.minefield.width(),
+            height: self.minefield.height(),
+            cells: self.board.clone(),
+        }
+    }
+
+    /// Every cell the solver opened or flagged so far, in order, with the reason why
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Probability that optimal play wins from the current position, by searching the whole
+    /// game tree. Errors on boards with more than `cell_limit` cells, or more than 64.
+    pub fn win_probability(&self, cell_limit: usize) -> Result<f64, SolverError> {
+        analysis::win_probability(
+            &self.board,
+            self.minefield.width(),
+            self.minefield.height(),
+            self.minefield.number_of_mines(),
+            self.minefield.topology(),
+            cell_limit,
+        )
+    }
+
+    /// Win probability of optimal play after opening each unknown cell, by searching the
+    /// whole game tree like `win_probability`. The best of these is the optimal guess.
+    pub fn guess_values(&self, cell_limit: usize) -> Result<Vec<(Pos, f64)>, SolverError> {
+        analysis::guess_values(
+            &self.board,
+            self.minefield.width(),
+            self.minefield.height(),
+            self.minefield.number_of_mines(),
+            self.minefield.topology(),
+            cell_limit,
+        )
+    }
+
+    /// Mine layout of a won game as native minefield, to turn a game on another backend, like
+    /// the Python one, into a reproducible fixture. Only a won game pins down every mine.
+    pub fn reconstruct_layout(&self) -> Result<RustMinefield, SolverError> {
+        if !matches!(
+            self.outcome,
+            Some(Outcome {
+                status: Status::Won,
+                ..
+            })
+        ) {
+            return Err(SolverError::LayoutUnknown);
+        }
+
+        // Every safe cell is open, so the rest are mines
+        let field = self
+            .board
+            .iter()
+            .map(|cell| !matches!(cell, Cell::Number(_) | Cell::Hidden))
+            .collect();
+        let minefield =
+            RustMinefield::from_layout(self.minefield.width(), self.minefield.height(), field)
+                .map_err(|e| SolverError::InvalidLayout {
+                    reason: e.to_string(),
+                })?;
+        Ok(minefield.with_topology(self.minefield.topology()))
+    }
+
+    /// Save the game to resume it later with `from_snapshot`. Errors for minefields that
+    /// can't provide their layout, like the Python one.
+    pub fn save(&self) -> Result<SolverSnapshot, SolverError> {
+        Ok(SolverSnapshot {
+            layout: self.minefield.saved_layout()?,
+            state: self.state(),
+        })
+    }
+
+    /// Resume a saved game, `minefield` has to be the one from `snapshot.layout`
+    pub fn from_snapshot(
+        minefield: &'a mut T,
+        options: SolverOptions,
+        snapshot: SolverSnapshot,
+    ) -> Result<Self, SolverError> {
+        let mut solver = Self::with_options(minefield, options)?;
+        let size = solver.board.len();
+        let state = &snapshot.state;
+        if state.board.len() != size || state.settled.len() != size || state.sweeps.len() != size {
+            return Err(SolverError::SnapshotMismatch);
+        }
+        solver.set_state(snapshot.state);
+        Ok(solver)
+    }
+
+    fn state(&self) -> SolverState {
+        SolverState {
+            board: self.board.clone(),
+            flags: self.flags,
+            unknowns: self.unknowns,
+            next: self.next.clone(),
+            settled: self.settled.clone(),
+            moves: self.moves.clone(),
+            sweeps: self.sweeps.clone(),
+            luck: self.luck,
+            log_luck: self.log_luck,
+            outcome: self.outcome,
+            regions: self.regions.clone(),
+            known_safe: self.known_safe.clone(),
+            flag_clicks: self.flag_clicks,
+        }
+    }
+
+    fn set_state(&mut self, state: SolverState) {
+        self.board = state.board;
+        self.flags = state.flags;
+        self.unknowns = state.unknowns;
+        self.next = state.next;
+        self.settled = state.settled;
+        self.moves = state.moves;
+        self.sweeps = state.sweeps;
+        self.luck = state.luck;
+        self.log_luck = state.log_luck;
+        self.outcome = state.outcome;
+        self.regions = state.regions;
+        self.known_safe = state.known_safe;
+        self.flag_clicks = state.flag_clicks;
+        self.changes.clear();
+    }
+
+    /// Sweeps a player would have needed for the cells opened so far, where opening a `0`
+    /// also opens its neighbors for free. Flags don't count, like the no flagging style of
+    /// play that 3BV assumes.
+    pub fn clicks(&self) -> usize {
+        let mut zeros: Vec<Pos> = Vec::new();
+        let mut clicks = 0;
+        for m in &self.moves {
+            if m.cell == Cell::Flag {
+                continue;
+            }
+            let free = self
+                .minefield
+                .topology()
+                .neighbors(m.pos)
+                .iter()
+                .any(|n| zeros.contains(n));
+            if !free {
+                clicks += 1;
+            }
+            if m.cell == Cell::Number(0) {
+                zeros.push(m.pos);
+            }
+        }
+        clicks
+    }
+
+    /// Flag moves that cost a click, every one unless `SolverOptions::flagging` skips some
+    pub fn flag_clicks(&self) -> usize {
+        self.flag_clicks
+    }
+
+    /// 3BV of the board, the fewest clicks that solve it, when the minefield knows its layout
+    pub fn three_bv(&self) -> Option<usize> {
+        self.minefield.three_bv()
+    }
+
+    /// Guesses made so far, not counting guesses on cells that were certainly safe
+    pub fn guesses(&self) -> usize {
+        self.moves
+            .iter()
+            .filter(|m| matches!(m.reason, Reason::Guess { p } if p > 0f32))
+            .count()
+    }
+
+    /// Calls to `Minefield::sweep_cell` so far
+    pub fn sweeps(&self) -> usize {
+        self.sweeps.iter().map(|&n| usize::from(n)).sum()
+    }
+
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// Product of the chances to survive the guesses so far
+    pub fn luck(&self) -> f32 {
+        self.luck
+    }
+
+    pub fn mines_remaining(&self) -> i32 {
+        self.minefield.number_of_mines() - self.flags
+    }
+
+    /// Add a hint that `region.cells` hold `region.mines` mines, for puzzles that annotate
+    /// parts of the board with a mine count
+    pub fn add_region(&mut self, region: Region) -> Result<(), SolverError> {
+        let mut indices = Vec::new();
+        for pos in region.cells.iter().copied() {
+            let i = self.index(pos).ok_or(SolverError::OutOfBounds { pos })?;
+            if indices.contains(&i) {
+                return Err(SolverError::RegionCellTwice { pos });
+            }
+            indices.push(i);
+        }
+        if !matches!(usize::try_from(region.mines), Ok(mines) if mines <= region.cells.len()) {
+            return Err(SolverError::RegionMines {
+                cells: region.cells.len(),
+                mines: region.mines,
+            });
+        }
+
+        self.regions.push(region);
+        Ok(())
+    }
+
+    /// Put a cell that is already known from elsewhere on the board, without sweeping it.
+    ///
+    /// Revealed numbers replace the default opening, so the solver continues from them.
+    pub fn reveal(&mut self, pos: Pos, cell: Cell) -> Result<(), SolverError> {
+        let i = self.index(pos).ok_or(SolverError::OutOfBounds { pos })?;
+        if self.board[i] != Cell::Unknown {
+            return Err(SolverError::AlreadyKnown { pos });
+        }
+
+        match cell {
+            Cell::Number(_) => {
+                self.drop_first_guess();
+                self.next.push(pos);
+            }
+            // Safe, but without a number there is nothing to deduce from
+            Cell::Hidden => self.drop_first_guess(),
+            Cell::Flag if self.known_safe.contains(&pos) => {
+                return Err(SolverError::KnownSafe { pos })
+            }
+            Cell::Flag => self.flags += 1,
+            _ => return Err(SolverError::CantReveal { cell }),
+        }
+
+        self.board[i] = cell;
+        self.unknowns -= 1;
+        Ok(())
+    }
+
+    /// Remember that `pos` is safe without sweeping it yet, from an earlier session for
+    /// example. The solver opens these cells first, instead of the default opening.
+    pub fn mark_safe(&mut self, pos: Pos) -> Result<(), SolverError> {
+        self.check_unknown(pos)?;
+        if self.known_safe.is_empty() {
+            self.drop_first_guess();
+        }
+        if !self.known_safe.contains(&pos) {
+            self.known_safe.push(pos);
+            self.next.push(pos);
+        }
+        Ok(())
+    }
+
+    /// Forget the first guess if it is still pending, only opened cells and cells marked safe
+    /// are known to be safe
+    fn drop_first_guess(&mut self) {
+        let (width, height) = (self.minefield.width(), self.minefield.height());
+        let (board, known_safe) = (&self.board, &self.known_safe);
+        self.next.retain(|pos| {
+            known_safe.contains(pos)
+                || matches!(cell_index(*pos, width, height), Some(i) if board[i] != Cell::Unknown)
+        });
+    }
+
+    /// Sweep `pos` for a player. A `0` also opens its neighbors, like in the classic game.
+    /// The game is lost on a mine and won once every safe cell is open.
+    pub fn sweep(&mut self, pos: Pos) -> Result<Cell, SolverError> {
+        if self.outcome.is_some() {
+            return Err(SolverError::GameOver);
+        }
+        self.check_unknown(pos)?;
+        self.drop_first_guess();
+
+        let cell = self.uncover(pos, Reason::Player)?;
+        if cell == Cell::Mine {
+            self.finish(Status::Exploded { at: pos });
+            return Ok(cell);
+        }
+
+        let mut open = vec![(pos, cell)];
+        while let Some((pos, cell)) = open.pop() {
+            self.next.push(pos);
+            if cell == Cell::Number(0) {
+                for n in self.unknown_neighbors(pos) {
+                    open.push((n, self.uncover(n, Reason::Player)?));
+                }
+            }
+        }
+
+        let opened = self
+            .board
+            .iter()
+            .filter(|cell| matches!(cell, Cell::Number(_) | Cell::Hidden))
+            .count();
+        let mines = usize::try_from(self.minefield.number_of_mines()).unwrap_or_default();
+        if opened + mines == self.board.len() {
+            self.finish(Status::Won);
+        }
+        self.changes.clear();
+        Ok(cell)
+    }
+
+    /// Flag `pos` for a player, or take the flag away again
+    pub fn toggle_flag(&mut self, pos: Pos) -> Result<(), SolverError> {
+        match self.get(pos) {
+            Some(Cell::Unknown) => self.plant_flag(pos, Reason::Player)?,
+            Some(Cell::Flag) => {
+                let i = self.index(pos).ok_or(SolverError::OutOfBounds { pos })?;
+                self.board[i] = Cell::Unknown;
+                self.flags -= 1;
+                self.unknowns += 1;
+                // The numbers around it have an unknown neighbor again
+                for n in self.minefield.topology().neighbors(pos) {
+                    if let Some(i) = self.index(n) {
+                        self.settled[i] = false;
+                        self.next.push(n);
+                    }
+                }
+            }
+            Some(_) => return Err(SolverError::AlreadyKnown { pos }),
+            None => return Err(SolverError::OutOfBounds { pos }),
+        }
+        self.changes.clear();
+        Ok(())
+    }
+
+    /// Safest cell to sweep next with its mine probability, treating the flags as mines
+    pub fn hint(&self) -> Result<(Pos, f32), SolverError> {
+        self.best_guess(&self.guess_probabilities())
+    }
+
+    /// Move that `step` would make next, without making it. Looks at the cells the next
+    /// deduction pass goes over in the same order, so it doesn't touch the minefield.
+    pub fn suggest(&self) -> Result<Suggestion, SolverError> {
+        if self.outcome.is_some() {
+            return Err(SolverError::GameOver);
+        }
+
+        // First certain move of the active cells, the deduction order picks between opening
+        // and flagging first
+        let mut certain = self.next.iter().filter_map(|pos| {
+            let pos = *pos;
+            if self.is_settled(pos) {
+                return None;
+            }
+            match self.get(pos)? {
+                Cell::Number(number) => {
+                    let constraint = self.number_constraint(pos, number);
+                    let first = *constraint.unknowns.first()?;
+                    match constraint.decided()? {
+                        false => Some(Suggestion::Safe {
+                            pos: first,
+                            reason: Reason::Satisfied { by: pos },
+                        }),
+                        true => Some(Suggestion::Mine {
+                            pos: first,
+                            reason: Reason::OnlyMines { by: pos },
+                        }),
+                    }
+                }
+                Cell::Unknown => Some(Suggestion::Safe {
+                    pos,
+                    reason: if self.known_safe.contains(&pos) {
+                        Reason::KnownSafe
+                    } else {
+                        Reason::Opening
+                    },
+                }),
+                _ => None,
+            }
+        });
+        let found = match self.options.deduce_order {
+            DeduceOrder::Mixed => certain.next(),
+            DeduceOrder::UncoverFirst => {
+                let all: Vec<Suggestion> = certain.collect();
+                all.iter()
+                    .find(|s| matches!(s, Suggestion::Safe { .. }))
+                    .or_else(|| all.first())
+                    .copied()
+            }
+            DeduceOrder::FlagFirst => {
+                let all: Vec<Suggestion> = certain.collect();
+                all.iter()
+                    .find(|s| matches!(s, Suggestion::Mine { .. }))
+                    .or_else(|| all.first())
+                    .copied()
+            }
+        };
+        if let Some(found) = found {
+            return Ok(found);
+        }
+
+        for (region, hint) in self.regions.iter().enumerate() {
+            let constraint = self.region_constraint(hint);
+            if let (Some(pos), Some(mines)) = (constraint.unknowns.first(), constraint.decided()) {
+                let (pos, reason) = (*pos, Reason::Region { region });
+                return Ok(match mines {
+                    false => Suggestion::Safe { pos, reason },
+                    true => Suggestion::Mine { pos, reason },
+                });
+            }
+        }
+
+        let remaining_mines = self.mines_remaining();
+        let first_unknown = self
+            .sweep_positions()
+            .into_iter()
+            .find(|pos| self.get(*pos) == Some(Cell::Unknown));
+        match first_unknown {
+            Some(pos) if remaining_mines == 0 => {
+                return Ok(Suggestion::Safe {
+                    pos,
+                    reason: Reason::AllMinesFlagged,
+                })
+            }
+            Some(pos) if remaining_mines == self.unknowns => {
+                return Ok(Suggestion::Mine {
+                    pos,
+                    reason: Reason::OnlyMinesLeft,
+                })
+            }
+            _ => (),
+        }
+
+        let (pos, p) = self.best_guess(&self.guess_probabilities())?;
+        Ok(Suggestion::Guess { pos, p })
+    }
+
+    /// Check that the revealed numbers don't contradict each other. Every number must be at
+    /// least its flagged and exploded neighbors, and at most those plus its unknown neighbors.
+    pub fn check_consistency(&self) -> Result<(), SolverError> {
+        for pos in self.positions() {
+            if let Some(Cell::Number(number)) = self.get(pos) {
+                let neighbors = self.neighbors(pos);
+                let count = |f: fn(&Cell) -> bool| -> i32 {
+                    neighbors
+                        .iter()
+                        .filter(|(_, cell)| f(cell))
+                        .count()
+                        .try_into()
+                        .unwrap()
+                };
+                let mines = count(|cell| matches!(cell, Cell::Flag | Cell::Mine));
+                let unknowns = count(|cell| matches!(cell, Cell::Unknown));
+
+                if mines > number.into() || i32::from(number) > mines + unknowns {
+                    return Err(SolverError::Inconsistent {
+                        pos,
+                        number,
+                        mines,
+                        unknowns,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index(&self, pos: Pos) -> Option<usize> {
+        cell_index(pos, self.minefield.width(), self.minefield.height())
+    }
+
+    /// Cell at `pos` as the solver sees it, `None` off the board
+    pub fn get(&self, pos: Pos) -> Option<Cell> {
+        self.index(pos).map(|i| self.board[i])
+    }
+
+    fn check_unknown(&self, pos: Pos) -> Result<(), SolverError> {
+        match self.get(pos) {
+            Some(Cell::Unknown) => Ok(()),
+            Some(_) => Err(SolverError::AlreadyKnown { pos }),
+            None => Err(SolverError::OutOfBounds { pos }),
+        }
+    }
+
+    fn uncover(&mut self, pos: Pos, reason: Reason) -> Result<Cell, SolverError> {
+        let i = self.index(pos).ok_or(SolverError::OutOfBounds { pos })?;
+        // Check before sweeping, every sweep can be a round trip to Python
+        assert!(self.board[i] == Cell::Unknown);
+        debug_assert_eq!(self.sweeps[i], 0, "Cell {:?} swept twice", pos);
+
+        let Pos(col, row) = pos;
+        let cell = self.minefield.sweep_cell(col, row)?;
+        self.sweeps[i] += 1;
+        self.board[i] = cell;
+        self.unknowns -= 1;
+        self.changes.push((pos, cell));
+        self.moves.push(Move { pos, cell, reason });
+        Ok(cell)
+    }
+
+    fn plant_flag(&mut self, pos: Pos, reason: Reason) -> Result<(), SolverError> {
+        let i = self.index(pos).ok_or(SolverError::OutOfBounds { pos })?;
+        assert!(self.board[i] == Cell::Unknown);
+        if self.known_safe.contains(&pos) {
+            return Err(SolverError::KnownSafe { pos });
+        }
+        // Checked before the flag, the number still counts this cell as unknown
+        let click = match self.options.flagging {
+            Flagging::All => true,
+            Flagging::LoadBearing => reason == Reason::Player || self.load_bearing(pos),
+        };
+        if click {
+            self.flag_clicks += 1;
+        }
+        self.board[i] = Cell::Flag;
+        self.flags += 1;
+        self.unknowns -= 1;
+        self.changes.push((pos, Cell::Flag));
+        self.moves.push(Move {
+            pos,
+            cell: Cell::Flag,
+            reason,
+        });
+        Ok(())
+    }
+
+    /// Whether `pos` is the last mine some neighboring number needs while that number has other
+    /// unknown neighbors, which the flag then clears
+    fn load_bearing(&self, pos: Pos) -> bool {
+        self.neighbors(pos)
+            .into_iter()
+            .any(|(number_pos, cell)| match cell {
+                Cell::Number(number) => {
+                    let constraint = self.number_constraint(number_pos, number);
+                    constraint.mines_remaining == 1 && constraint.unknowns.len() > 1
+                }
+                _ => false,
+            })
+    }
+
+    fn neighbors(&self, pos: Pos) -> Vec<(Pos, Cell)> {
+        self.minefield
+            .topology()
+            .neighbors(pos)
+            .into_iter()
+            .filter_map(|pos| self.get(pos).map(|cell| (pos, cell)))
+            .collect()
+    }
+
+    /// Lowest revealed number next to `pos`
+    fn lowest_number_around(&self, pos: Pos) -> Option<u8> {
+        self.neighbors(pos)
+            .into_iter()
+            .filter_map(|(_, cell)| match cell {
+                Cell::Number(number) => Some(number),
+                _ => None,
+            })
+            .min()
+    }
+
+    fn unknown_neighbors(&self, pos: Pos) -> Vec<Pos> {
+        self.neighbors(pos)
+            .iter()
+            .filter(|(_, cell)| matches!(cell, Cell::Unknown))
+            .map(|(pos, _)| *pos)
+            .collect()
+    }
+
+    fn flagged_neighbors(&self, pos: Pos) -> i32 {
+        self.neighbors(pos)
+            .iter()
+            .filter(|(_, cell)| matches!(cell, Cell::Flag))
+            .count()
+            .try_into()
+            .unwrap()
+    }
+
+    fn is_settled(&self, pos: Pos) -> bool {
+        matches!(self.index(pos), Some(i) if self.settled[i])
+    }
+
+    fn settle(&mut self, pos: Pos) {
+        if let Some(i) = self.index(pos) {
+            self.settled[i] = true;
+        }
+    }
+
+    /// Constraint of the number at `pos`
+    fn number_constraint(&self, pos: Pos, number: u8) -> Constraint {
+        Constraint {
+            unknowns: self.unknown_neighbors(pos),
+            mines_remaining: i32::from(number) - self.flagged_neighbors(pos),
+        }
+    }
+
+    /// Constraint of region hint `region`
+    fn region_constraint(&self, region: &Region) -> Constraint {
+        let mut unknowns = Vec::new();
+        let mut mines_remaining = region.mines;
+        for pos in region.cells.iter().copied() {
+            match self.get(pos) {
+                Some(Cell::Unknown) => unknowns.push(pos),
+                Some(Cell::Flag) => mines_remaining -= 1,
+                _ => (),
+            }
+        }
+        Constraint {
+            unknowns,
+            mines_remaining,
+        }
+    }
+
+    /// Constraints of the numbers and region hints that still have unknown cells
+    fn constraints(&self) -> Vec<Constraint> {
+        self.active_constraints()
+            .into_iter()
+            .map(|(_, constraint)| constraint)
+            .collect()
+    }
+
+    /// Constraints of the numbers and region hints that still have unknown cells, with the
+    /// position of the number they come from, `None` for a region hint
+    pub fn active_constraints(&self) -> Vec<(Option<Pos>, Constraint)> {
+        let mut constraints = Vec::new();
+        for pos in self.positions() {
+            if let Some(Cell::Number(number)) = self.get(pos) {
+                if !self.is_settled(pos) {
+                    constraints.push((Some(pos), self.number_constraint(pos, number)));
+                }
+            }
+        }
+        constraints.extend(
+            self.regions
+                .iter()
+                .map(|region| (None, self.region_constraint(region))),
+        );
+        constraints.retain(|(_, constraint)| !constraint.unknowns.is_empty());
+        constraints
+    }
+
+    /// Approximate mine probability for every unknown cell next to a revealed number.
+    ///
+    /// Runs the same iterative relaxation that `solve` uses to pick a guess, over the
+    /// current border. Unknown cells that don't touch a revealed number are not in the map.
+    pub fn remaining_mine_estimate(&self) -> HashMap<Pos, f32> {
+        relax(&self.constraints(), self.mines_remaining(), self.unknowns).0
+    }
+
+    /// Exact mine probabilities of the border and the other unknowns, `None` when the search
+    /// gets too big
+    fn exact_mine_estimate(&self) -> Option<(HashMap<Pos, f32>, Option<f32>)> {
+        let constraints = self.constraints();
+        let remaining_mines = self.mines_remaining();
+        csp::marginals(
+            &constraints,
+            remaining_mines,
+            self.unknowns,
+            self.options.csp_node_limit,
+        )
+        .map(|marginals| (marginals.probs, marginals.other))
+    }
+
+    /// Every unknown cell that is certainly safe, by the exact solver, sorted. What a perfect
+    /// player can open without risk. `None` when the search gets bigger than
+    /// `csp_node_limit`.
+    pub fn safe_cells(&self) -> Option<Vec<Pos>> {
+        self.certain_cells(0f32)
+    }
+
+    /// Every unknown cell that is certainly a mine, by the exact solver, sorted. `None` when
+    /// the search gets bigger than `csp_node_limit`.
+    pub fn mine_cells(&self) -> Option<Vec<Pos>> {
+        self.certain_cells(1f32)
+    }
+
+    /// Unknown cells with exact mine probability `p`
+    fn certain_cells(&self, p: f32) -> Option<Vec<Pos>> {
+        let (probs, p_other) = self.exact_mine_estimate()?;
+        let mut cells: Vec<Pos> = self
+            .positions()
+            .filter(|pos| self.get(*pos) == Some(Cell::Unknown))
+            .filter(|pos| probs.get(pos).copied().or(p_other) == Some(p))
+            .collect();
+        cells.sort();
+        Some(cells)
+    }
+
+    /// Groups of the unknown cells next to a revealed number or in a region hint, such that no
+    /// number or hint spans two groups. Each group is an independent part of the board.
+    /// Sorted cells, the biggest group first.
+    pub fn border_components(&self) -> Vec<Vec<Pos>> {
+        csp::components(&self.constraints())
+    }
+
+    /// Two unknown cells that are a coin flip no sweep can settle: both are a mine with exact
+    /// probability 1/2, every constraint mentions both or neither, and the cells next to only
+    /// one of them are known or certainly mines. `None` when the exact solver gives up.
+    pub fn fifty_fifty(&self) -> Option<(Pos, Pos)> {
+        let (probs, _) = self.exact_mine_estimate()?;
+        let constraints = self.constraints();
+        let mut halves: Vec<Pos> = probs
+            .iter()
+            .filter(|(_, p)| (**p - 0.5).abs() < 1e-6)
+            .map(|(pos, _)| *pos)
+            .collect();
+        halves.sort();
+
+        let topology = self.minefield.topology();
+        for (i, a) in halves.iter().copied().enumerate() {
+            for b in halves[i + 1..].iter().copied() {
+                let alike = constraints.iter().all(|constraint| {
+                    constraint.unknowns.contains(&a) == constraint.unknowns.contains(&b)
+                });
+                let (around_a, around_b) = (topology.neighbors(a), topology.neighbors(b));
+                let only_a = around_a.iter().filter(|n| !around_b.contains(n));
+                let only_b = around_b.iter().filter(|n| !around_a.contains(n));
+                // Sweeping one of these would tell the two apart
+                let telling = only_a.chain(only_b).any(|n| {
+                    *n != a
+                        && *n != b
+                        && self.get(*n) == Some(Cell::Unknown)
+                        && probs.get(n) != Some(&1f32)
+                });
+                if alike && !telling {
+                    return Some((a, b));
+                }
+            }
+        }
+        None
+    }
+
+    /// Mine probabilities used to pick a guess, exact when feasible, approximated otherwise
+    fn guess_probabilities(&self) -> GuessProbabilities {
+        if let Some((border, other)) = self.exact_mine_estimate() {
+            return GuessProbabilities {
+                border,
+                other,
+                relax_iterations: None,
+            };
+        }
+
+        self.relaxed_probabilities()
+    }
+
+    /// Mine probabilities approximated by `relax`
+    fn relaxed_probabilities(&self) -> GuessProbabilities {
+        let (probs, iterations) = relax(&self.constraints(), self.mines_remaining(), self.unknowns);
+        let remaining_mines = self.mines_remaining();
+
+        let sum = ordered_sum(&probs);
+        let border_unknowns: i32 = probs.len().try_into().unwrap();
+        let isolated_unknowns: i32 = self.unknowns - border_unknowns;
+
+        // Mine probability of the unknowns that don't touch a number, if there are any
+        let p_other = (isolated_unknowns > 0)
+            .then(|| (remaining_mines as f32 - sum) / (isolated_unknowns as f32));
+
+        GuessProbabilities {
+            border: probs,
+            other: p_other,
+            relax_iterations: Some(iterations),
+        }
+    }
+
+    pub fn solve(&mut self) -> Result<Outcome, SolverError> {
+        self.solve_observed(None)
+    }
+
+    /// Like `solve`, but calls `observer` with every cell the solver opens or flags, in order.
+    /// The observer sees the events of a step once the step is done.
+    pub fn solve_observed(
+        &mut self,
+        mut observer: Option<&mut dyn FnMut(&SolveEvent)>,
+    ) -> Result<Outcome, SolverError> {
+        loop {
+            let seen = self.moves.len();
+            self.step()?;
+            if let Some(observer) = observer.as_mut() {
+                for m in &self.moves[seen..] {
+                    observer(&SolveEvent::from(m));
+                }
+            }
+            if let Some(outcome) = self.outcome {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Result of the game, `None` while it is still in progress
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    fn finish(&mut self, status: Status) {
+        self.outcome = Some(Outcome {
+            status,
+            luck: self.luck,
+            log_luck: self.log_luck,
+        });
+    }
+
+    /// Score every unknown cell by its chance to be safe times the constraints it is expected
+    /// to add: its own number, plus a number for each unknown neighbor when it turns out to be
+    /// a zero. The chance of a zero treats the neighbors as independent. Cells that are
+    /// certainly safe always go first. Cells in `skip` are never picked.
+    fn progress_guess(
+        &self,
+        probs: &HashMap<Pos, f32>,
+        p_other: Option<f32>,
+        skip: &HashSet<Pos>,
+    ) -> Option<(Pos, f32)> {
+        let p = |pos: &Pos| probs.get(pos).copied().or(p_other);
+
+        let mut best: Option<(Pos, f32, (bool, f32))> = None;
+        for pos in self.positions().filter(|pos| !skip.contains(pos)) {
+            let p_mine = match (self.get(pos), p(&pos)) {
+                (Some(Cell::Unknown), Some(p_mine)) => p_mine,
+                _ => continue,
+            };
+
+            let neighbors = self.unknown_neighbors(pos);
+            let p_zero: f32 = neighbors
+                .iter()
+                .map(|n| 1f32 - p(n).unwrap_or(0f32))
+                .product();
+            let score = (
+                p_mine == 0f32,
+                (1f32 - p_mine) * (1f32 + p_zero * neighbors.len() as f32),
+            );
+
+            if !matches!(best, Some((_, _, best_score)) if best_score >= score) {
+                best = Some((pos, p_mine, score));
+            }
+        }
+
+        best.map(|(pos, p, _)| (pos, p))
+    }
+
+    /// Finish a game without unknown cells left
+    fn finish_uncovered(&mut self) {
+        if self.solved() {
+            self.finish(Status::Won);
+            return;
+        }
+
+        // Everything uncovered without exploding, the flags don't match the mines. `info()`
+        // reports it with `flag_mismatch`.
+        self.finish(Status::Incomplete);
+    }
+
+    /// Run one pass of deductions, or make a guess when the deductions are stuck.
+    ///
+    /// Returns the cells that changed during this step, so a renderer only has to redraw
+    /// those. Once the game is over this does nothing and returns no changes.
+    pub fn step(&mut self) -> Result<Vec<(Pos, Cell)>, SolverError> {
+        if let Some(changes) = self.deduce()? {
+            return Ok(changes);
+        }
+
+        if self.options.logic_only {
+            self.finish(Status::Incomplete);
+            return Ok(std::mem::take(&mut self.changes));
+        }
+
+        let best_guess = self.pick_guess()?;
+        if self.options.strict_no_guess && best_guess.1 > 0f32 {
+            return Err(SolverError::GuessRequired { at: best_guess.0 });
+        }
+        self.guess(best_guess)
+    }
+
+    /// Solve as far as possible without guessing, a guess on a cell that is certainly safe
+    /// doesn't count. Returns whether the board got solved; if not, `solve` or `step` can
+    /// continue from where the deductions got stuck.
+    pub fn solve_without_guessing(&mut self) -> Result<bool, SolverError> {
+        loop {
+            if let Some(outcome) = self.outcome {
+                return Ok(outcome.status == Status::Won);
+            }
+            if self.deduce()?.is_none() {
+                let best_guess = self.pick_guess()?;
+                if best_guess.1 > 0f32 {
+                    return Ok(false);
+                }
+                self.guess(best_guess)?;
+            }
+        }
+    }
+
+    /// One pass of deductions. Returns the changes when it made progress or ended the game,
+    /// `None` when a guess is needed.
+    fn deduce(&mut self) -> Result<Option<Vec<(Pos, Cell)>>, SolverError> {
+        if self.outcome.is_none() && self.budget_spent() {
+            if self.unknowns == 0 {
+                self.finish_uncovered();
+            } else {
+                self.finish(Status::Incomplete);
+            }
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        let start = Instant::now();
+        let result = self.deduction_pass();
+        self.timings.deduction += start.elapsed();
+        result
+    }
+
+    fn deduction_pass(&mut self) -> Result<Option<Vec<(Pos, Cell)>>, SolverError> {
+        if self.outcome.is_some() {
+            return Ok(Some(Vec::new()));
+        }
+
+        let active = std::mem::take(&mut self.next);
+        let mut new_info = false;
+
+        // Which decided numbers each round over the active cells handles, `Some(true)` for
+        // the ones that flag and `Some(false)` for the ones that uncover
+        let rounds: &[Option<bool>] = match self.options.deduce_order {
+            DeduceOrder::Mixed => &[None],
+            DeduceOrder::UncoverFirst => &[Some(false), Some(true)],
+            DeduceOrder::FlagFirst => &[Some(true), Some(false)],
+        };
+        'rounds: for (round, only) in rounds.iter().copied().enumerate() {
+            let last = round + 1 == rounds.len();
+            for pos in active.iter().copied() {
+                if self.budget_spent() {
+                    break 'rounds;
+                }
+                let cell = self.get(pos).ok_or(SolverError::OutOfBounds { pos })?;
+                if self.is_settled(pos) {
+                    continue;
+                }
+
+                match cell {
+                    Cell::Number(number) => {
+                        let constraint = self.number_constraint(pos, number);
+                        if constraint.unknowns.is_empty() {
+                            self.settle(pos);
+                            continue;
+                        }
+
+                        // Numbers for another round wait like undecided ones
+                        let decided = constraint
+                            .decided()
+                            .filter(|mines| only.is_none() || only == Some(*mines));
+                        match decided {
+                            Some(false) => {
+                                for p in constraint.unknowns {
+                                    if self.budget_spent() {
+                                        break;
+                                    }
+                                    self.uncover(p, Reason::Satisfied { by: pos })?;
+                                    self.next.push(p);
+                                }
+                                // Out of moves halfway, the number stays active
+                                if !self.budget_spent() {
+                                    self.settle(pos);
+                                }
+                                new_info = true;
+                            }
+                            Some(true) => {
+                                for p in constraint.unknowns {
+                                    if self.budget_spent() {
+                                        break;
+                                    }
+                                    self.plant_flag(p, Reason::OnlyMines { by: pos })?;
+                                }
+                                // Out of moves halfway, the number stays active
+                                if !self.budget_spent() {
+                                    self.settle(pos);
+                                }
+                                new_info = true;
+                            }
+                            None if last => self.next.push(pos),
+                            None => (),
+                        }
+                    }
+                    Cell::Unknown => {
+                        let reason = if self.known_safe.contains(&pos) {
+                            Reason::KnownSafe
+                        } else {
+                            Reason::Opening
+                        };
+                        self.uncover(pos, reason)?;
+                        // A later round sees it as a number
+                        if last {
+                            self.next.push(pos);
+                        }
+                        new_info = true;
+                    }
+                    Cell::Mine => {
+                        self.finish(Status::Exploded { at: pos });
+                        return Ok(Some(std::mem::take(&mut self.changes)));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // Out of moves, keep the active cells for when the game continues anyway
+        if self.budget_spent() {
+            self.next.extend(active);
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        // Region hints work like numbers that aren't on the board
+        for region in 0..self.regions.len() {
+            let constraint = self.region_constraint(&self.regions[region]);
+            if constraint.unknowns.is_empty() {
+                continue;
+            }
+            match constraint.decided() {
+                Some(false) => {
+                    for p in constraint.unknowns {
+                        if self.budget_spent() {
+                            break;
+                        }
+                        self.uncover(p, Reason::Region { region })?;
+                        self.next.push(p);
+                    }
+                    new_info = true;
+                }
+                Some(true) => {
+                    for p in constraint.unknowns {
+                        if self.budget_spent() {
+                            break;
+                        }
+                        self.plant_flag(p, Reason::Region { region })?;
+                    }
+                    new_info = true;
+                }
+                None => (),
+            }
+        }
+
+        // Already done
+        if self.unknowns == 0 {
+            self.finish_uncovered();
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        let remaining_mines = self.mines_remaining();
+
+        // Uncover remaining cells when all mines are flagged, then we are done. Checks each
+        // cell right before the sweep, a sweep that opens more than one cell doesn't lead to
+        // sweeping those again.
+        if remaining_mines == 0 {
+            for pos in self.sweep_positions() {
+                if self.budget_spent() {
+                    return Ok(Some(std::mem::take(&mut self.changes)));
+                }
+                if let Some(Cell::Unknown) = self.get(pos) {
+                    self.uncover(pos, Reason::AllMinesFlagged)?;
+                }
+            }
+            self.finish_uncovered();
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        // The other way around, every unknown cell left is a mine
+        if remaining_mines == self.unknowns {
+            for pos in self.sweep_positions() {
+                if self.budget_spent() {
+                    return Ok(Some(std::mem::take(&mut self.changes)));
+                }
+                if let Some(Cell::Unknown) = self.get(pos) {
+                    self.plant_flag(pos, Reason::OnlyMinesLeft)?;
+                }
+            }
+            self.finish_uncovered();
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        if new_info {
+            return Ok(Some(std::mem::take(&mut self.changes)));
+        }
+
+        // Simple algo didn't find new info
+        Ok(None)
+    }
+
+    /// Cells in the order of the final reveal, see `SolverOptions::sweep_order`
+    fn sweep_positions(&self) -> Vec<Pos> {
+        let (width, height) = (self.minefield.width(), self.minefield.height());
+        self.options.sweep_order.positions(width, height)
+    }
+
+    /// Whether the moves of `SolverOptions::move_budget` are used up
+    fn budget_spent(&self) -> bool {
+        matches!(self.options.move_budget, Some(budget) if self.moves.len() >= budget)
+    }
+
+    /// Cell to guess with its mine probability, using the heavier algos
+    fn pick_guess(&mut self) -> Result<(Pos, f32), SolverError> {
+        if self.options.track_missed_deductions {
+            self.record_missed_deductions();
+        }
+
+        let start = Instant::now();
+        let probabilities = self.guess_probabilities();
+        if let Some(iterations) = probabilities.relax_iterations {
+            self.relax_iterations.push(iterations);
+        }
+        let result = self.best_guess(&probabilities);
+        self.timings.guessing += start.elapsed();
+        result
+    }
+
+    /// Iterations the relaxation took for each guess that couldn't use the exact solver
+    pub fn relax_iterations(&self) -> &[usize] {
+        &self.relax_iterations
+    }
+
+    /// Remember the border cells that the exact solver finds certainly safe or certainly a
+    /// mine, while the deduction passes are stuck. Only works when the exact search fits in
+    /// `csp_node_limit`.
+    fn record_missed_deductions(&mut self) {
+        if let Some((probs, _)) = self.exact_mine_estimate() {
+            let mut missed: Vec<Pos> = probs
+                .into_iter()
+                .filter(|(pos, p)| (*p == 0f32 || *p == 1f32) && !self.missed.contains(pos))
+                .map(|(pos, _)| pos)
+                .collect();
+            missed.sort();
+            self.missed.extend(missed);
+        }
+    }
+
+    /// Cells the deduction passes could have decided but guessed on or left for later, in
+    /// the order they were found. Empty unless `track_missed_deductions` is set.
+    pub fn missed_deductions(&self) -> &[Pos] {
+        &self.missed
+    }
+
+    fn best_guess(&self, probabilities: &GuessProbabilities) -> Result<(Pos, f32), SolverError> {
+        let (probs, p_other) = (&probabilities.border, probabilities.other);
+
+        if self.options.guess_strategy == GuessStrategy::Random {
+            return self.random_guess(probabilities);
+        }
+
+        // Sorted by probability then position, so ties don't depend on the HashMap order
+        let mut candidates: Vec<(&Pos, &f32)> = probs.iter().collect();
+        candidates.sort_by(|(pos1, p1), (pos2, p2)| p1.total_cmp(p2).then(pos1.cmp(pos2)));
+        // Every strategy picks from the candidates that survive the lookahead. When all of them
+        // contradict, the check is off somewhere and any guess beats none.
+        let mut contradicting = HashSet::new();
+        if self.options.lookahead {
+            let (constraints, remaining_mines) = (self.constraints(), self.mines_remaining());
+            let (consistent, rejected): (Vec<_>, Vec<_>) = candidates
+                .iter()
+                .partition(|(pos, _)| !contradicts_safe(&constraints, remaining_mines, **pos));
+            if !consistent.is_empty() {
+                candidates = consistent;
+                contradicting = rejected.into_iter().map(|(pos, _)| *pos).collect();
+            }
+        }
+        let best_guess = candidates.first().copied();
+
+        if self.options.guess_strategy == GuessStrategy::Progress {
+            return self
+                .progress_guess(probs, p_other, &contradicting)
+                .ok_or(SolverError::NothingToGuess);
+        }
+
+        // Lazy, an error when the probability of the other unknowns comes without any
+        let pos_other = |p_other| {
+            self.positions()
+                .find(|pos| {
+                    matches!(self.get(*pos), Some(Cell::Unknown)) && !probs.contains_key(pos)
+                })
+                .map(|pos| (pos, p_other))
+                .ok_or(SolverError::NoCellOffBorder)
+        };
+
+        match (best_guess, p_other) {
+            (Some((_, p)), Some(p_other)) if p_other < *p => pos_other(p_other),
+            (Some((_, p)), _) if self.options.guess_strategy == GuessStrategy::Cascade => {
+                // Low numbers have few mines around, likely more safe cells to open next. The
+                // candidates are sorted, so ties keep their position order.
+                let tied = candidates
+                    .iter()
+                    .take_while(|(_, p_tied)| **p_tied - *p < 1e-6);
+                let (pos, p) = tied
+                    .min_by_key(|(pos, _)| self.lowest_number_around(**pos).unwrap_or(u8::MAX))
+                    .unwrap();
+                Ok((**pos, **p))
+            }
+            (Some((_, p)), _) if self.options.guess_strategy == GuessStrategy::BiggestRegion => {
+                // What a guess shows says the most in a big group, where it constrains many
+                // cells at once
+                let sizes: HashMap<Pos, usize> = self
+                    .border_components()
+                    .into_iter()
+                    .flat_map(|group| {
+                        let size = group.len();
+                        group.into_iter().map(move |pos| (pos, size))
+                    })
+                    .collect();
+                let comparable = candidates
+                    .iter()
+                    .take_while(|(_, p_close)| **p_close - *p <= COMPARABLE_SAFETY);
+                let (pos, p) = comparable
+                    .min_by_key(|(pos, _)| Reverse(sizes.get(pos).copied().unwrap_or(0)))
+                    .unwrap();
+                Ok((**pos, **p))
+            }
+            (Some((pos, p)), _) => Ok((*pos, *p)),
+            (None, Some(p_other)) => pos_other(p_other),
+            (None, None) => Err(SolverError::NothingToGuess),
+        }
+    }
+
+    /// Unknown cell for `GuessStrategy::Random`, the probabilities only give its `p`
+    fn random_guess(&self, probabilities: &GuessProbabilities) -> Result<(Pos, f32), SolverError> {
+        let unknowns: Vec<Pos> = self
+            .positions()
+            .filter(|pos| matches!(self.get(*pos), Some(Cell::Unknown)))
+            .collect();
+        if unknowns.is_empty() {
+            return Err(SolverError::NothingToGuess);
+        }
+        let seed = self
+            .options
+            .guess_seed
+            .wrapping_add(self.moves.len() as u64);
+        let pos = unknowns[StdRng::seed_from_u64(seed).gen_range(0..unknowns.len())];
+        let p = match probabilities.border.get(&pos) {
+            Some(p) => *p,
+            None => probabilities.other.ok_or(SolverError::NoCellOffBorder)?,
+        };
+        Ok((pos, p))
+    }
+
+    /// Uncover a cell that has mine probability `p`
+    fn guess(&mut self, (pos, p): (Pos, f32)) -> Result<Vec<(Pos, Cell)>, SolverError> {
+        self.luck *= 1f32 - p;
+        self.log_luck += f64::from(1f32 - p).ln();
+
+        let cell = self.uncover(pos, Reason::Guess { p })?;
+        if let Cell::Mine = cell {
+            self.finish(Status::Exploded { at: pos });
+        } else {
+            self.next.push(pos);
+        }
+
+        Ok(std::mem::take(&mut self.changes))
+    }
+
+    pub fn solved(&self) -> bool {
+        let flags: i32 = self
+            .board
+            .iter()
+            .filter(|cell| matches!(cell, Cell::Flag))
+            .count()
+            .try_into()
+            .unwrap();
+        let unknowns: i32 = self
+            .board
+            .iter()
+            .filter(|cell| matches!(cell, Cell::Unknown))
+            .count()
+            .try_into()
+            .unwrap();
+        let mines: i32 = self
+            .board
+            .iter()
+            .filter(|cell| matches!(cell, Cell::Mine))
+            .count()
+            .try_into()
+            .unwrap();
+        unknowns == 0 && mines == 0 && flags == self.minefield.number_of_mines()
+    }
+
+    /// Draw the board as SVG image, a square per cell with the numbers in the classic colors
+    pub fn render_svg<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        const CELL: usize = 24;
+        let (width, height) = (self.minefield.width(), self.minefield.height());
+        let topology = self.minefield.topology();
+        // Indents count characters of `render`, where a cell takes two
+        let max_indent = (0..height)
+            .map(|row| topology.indent(row))
+            .max()
+            .unwrap_or(0);
+        let image_width = usize::try_from(width).unwrap() * CELL + max_indent * CELL / 2;
+        let image_height = usize::try_from(height).unwrap() * CELL;
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="monospace" font-size="{2}" font-weight="bold" text-anchor="middle">"#,
+            image_width,
+            image_height,
+            CELL * 2 / 3
+        )?;
+        for pos in self.positions() {
+            let Pos(col, row) = pos;
+            let x = usize::try_from(col).unwrap() * CELL + topology.indent(row) * CELL / 2;
+            let y = usize::try_from(row).unwrap() * CELL;
+            let (fill, text, color) = match self.get(pos).unwrap() {
+                Cell::Unknown => ("#bdbdbd", String::new(), "black"),
+                Cell::Flag => ("#bdbdbd", "F".to_string(), "#d00000"),
+                Cell::Mine => ("#ff4040", "X".to_string(), "black"),
+                Cell::Number(0) => ("#eeeeee", String::new(), "black"),
+                Cell::Hidden => ("#eeeeee", "?".to_string(), "#808080"),
+                Cell::Number(x) => {
+                    let color = match x {
+                        1 => "#0000ff",
+                        2 => "#008000",
+                        3 => "#ff0000",
+                        4 => "#000080",
+                        5 => "#800000",
+                        6 => "#008080",
+                        7 => "#000000",
+                        _ => "#808080",
+                    };
+                    ("#eeeeee", x.to_string(), color)
+                }
+            };
+            writeln!(
+                w,
+                r##"<rect x="{x}" y="{y}" width="{cell}" height="{cell}" fill="{fill}" stroke="#7b7b7b"/>"##,
+                x = x,
+                y = y,
+                cell = CELL,
+                fill = fill
+            )?;
+            if !text.is_empty() {
+                writeln!(
+                    w,
+                    r#"<text x="{}" y="{}" fill="{}">{}</text>"#,
+                    x + CELL / 2,
+                    y + CELL * 3 / 4,
+                    color,
+                    text
+                )?;
+            }
+        }
+        writeln!(w, "</svg>")
+    }
+
+    /// Print the board with colored flags and mines, see `render`
+    pub fn show<W: Write>(&self, w: &mut W, glyphs: &RenderStyle) -> io::Result<()> {
+        self.render(w, Render::Color, glyphs)
+    }
+
+    /// Print the board in the given style, with the mine counter on top
+    pub fn render<W: Write>(
+        &self,
+        w: &mut W,
+        style: Render,
+        glyphs: &RenderStyle,
+    ) -> io::Result<()> {
+        writeln!(w, "Mines remaining: {}", self.mines_remaining())?;
+        let (width, height) = (self.minefield.width(), self.minefield.height());
+        let topology = self.minefield.topology();
+        let heat = (style == Render::Heat).then(|| self.relaxed_probabilities());
+        let glyph = |pos: Pos| -> String {
+            let cell = self.get(pos).unwrap();
+            let plain = glyphs.glyph(cell);
+            match (cell, style) {
+                (Cell::Flag, Render::Color | Render::Heat) => plain.bold().cyan().to_string(),
+                (Cell::Mine, Render::Color | Render::Heat) => plain.bold().red().to_string(),
+                (Cell::Unknown, Render::Heat) => {
+                    let heat = heat.as_ref().unwrap();
+                    match heat.border.get(&pos).copied().or(heat.other) {
+                        Some(p) => {
+                            let red = (p.clamp(0f32, 1f32) * 255f32).round() as u8;
+                            plain.truecolor(red, 255 - red, 0).to_string()
+                        }
+                        None => plain,
+                    }
+                }
+                _ => plain,
+            }
+        };
+
+        if style != Render::Unicode {
+            for row in 0..height {
+                write!(w, "{}", " ".repeat(topology.indent(row)))?;
+                for col in 0..width {
+                    write!(w, "{} ", glyph(Pos(col, row)))?;
+                }
+                writeln!(w)?;
+            }
+            return Ok(());
+        }
+
+        // Column numbers on top, with a line for the tens on wide boards, and row numbers on
+        // the left
+        let label = (height - 1).to_string().len();
+        let max_indent = (0..height)
+            .map(|row| topology.indent(row))
+            .max()
+            .unwrap_or(0);
+        let cell = glyphs.cell_width() + 1;
+        let inner = 1 + max_indent + cell * usize::try_from(width).unwrap();
+        let margin = " ".repeat(label + 3);
+        if width > 10 {
+            write!(w, "{}", margin)?;
+            for col in 0..width {
+                match col % 10 {
+                    0 => write!(w, "{:<cell$}", col / 10 % 10, cell = cell)?,
+                    _ => write!(w, "{}", " ".repeat(cell))?,
+                }
+            }
+            writeln!(w)?;
+        }
+        write!(w, "{}", margin)?;
+        for col in 0..width {
+            write!(w, "{:<cell$}", col % 10, cell = cell)?;
+        }
+        writeln!(w)?;
+
+        writeln!(w, "{} ┌{}┐", " ".repeat(label), "─".repeat(inner))?;
+        for row in 0..height {
+            let indent = topology.indent(row);
+            write!(w, "{:>label$} │ {}", row, " ".repeat(indent), label = label)?;
+            for col in 0..width {
+                write!(w, "{} ", glyph(Pos(col, row)))?;
+            }
+            writeln!(w, "{}│", " ".repeat(max_indent - indent))?;
+        }
+        writeln!(w, "{} └{}┘", " ".repeat(label), "─".repeat(inner))
+    }
+}
+
+impl<'a, T: Minefield + Clone> Solver<'a, T> {
+    /// Capture the current progress, to go back to it later with `restore`
+    ///
+    /// Only available for minefields that can be cloned, remote backends like the Python one
+    /// can't be rolled back together with the solver.
+    pub fn snapshot(&self) -> SolverState {
+        self.state()
+    }
+
+    pub fn restore(&mut self, state: SolverState) {
+        self.set_state(state);
+    }
+}
+
+#[test]
+fn bla() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(
+        4,
+        4,
+        vec![
+            false, false, false, false, false, false, true, false, false, false, false, false,
+            true, false, false, true,
+        ],
+    )?;
+
+    let mut solver = Solver::new(&mut minefield)?;
+
+    solver.solve()?;
+    assert!(solver.solved());
+
+    Ok(())
+}
+
+#[test]
+fn csp_node_limit_fallback() -> Result<()> {
+    // Without any nodes to search the exact solver gives up at once, and every guess comes from
+    // the relaxation
+    let options = SolverOptions {
+        csp_node_limit: 0,
+        ..Default::default()
+    };
+    let mut minefield = RustMinefield::from_layout(
+        4,
+        4,
+        vec![
+            false, false, false, false, false, false, true, false, false, false, false, false,
+            true, false, false, true,
+        ],
+    )?;
+    let mut solver = Solver::with_options(&mut minefield, options)?;
+    assert_eq!(solver.safe_cells(), None);
+    solver.solve()?;
+    assert!(solver.solved());
+
+    // Seeded games still play to the end
+    let preset = Preset::new(16, 16, 40)?;
+    for seed in 0..10 {
+        let mut minefield = RustMinefield::with_seed(preset, seed);
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        solver.solve()?;
+        assert!(solver.outcome().is_some());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn settled_cells_skipped() -> Result<()> {
+    // Forgetting which cells are settled before every step visits them all again, which must
+    // not change a single move
+    let preset = Preset::new(16, 16, 40)?;
+    for seed in 0..20 {
+        let mut minefield = RustMinefield::with_seed(preset, seed);
+        let mut solver = Solver::new(&mut minefield)?;
+        let outcome = solver.solve()?;
+        let moves = solver.moves().to_vec();
+
+        let mut minefield = RustMinefield::with_seed(preset, seed);
+        let mut solver = Solver::new(&mut minefield)?;
+        while solver.outcome().is_none() {
+            solver.settled.fill(false);
+            solver.step()?;
+        }
+        assert_eq!(solver.outcome(), Some(outcome));
+        assert_eq!(solver.moves(), &moves[..]);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn remaining_mine_estimate() -> Result<()> {
+    // Everything but the bottom right corner revealed, which has to be the mine
+    let mut minefield = RustMinefield::from_layout(
+        3,
+        3,
+        vec![
+            false, false, false, //
+            false, false, false, //
+            false, false, true, //
+        ],
+    )?;
+
+    let mut solver = Solver::new(&mut minefield)?;
+    assert!(solver.remaining_mine_estimate().is_empty());
+
+    for pos in [
+        Pos(0, 0),
+        Pos(1, 0),
+        Pos(2, 0),
+        Pos(0, 1),
+        Pos(1, 1),
+        Pos(2, 1),
+        Pos(0, 2),
+        Pos(1, 2),
+    ] {
+        solver.uncover(pos, Reason::Opening)?;
+    }
+
+    let probs = solver.remaining_mine_estimate();
+    assert_eq!(probs.len(), 1);
+    assert!((probs[&Pos(2, 2)] - 1f32).abs() < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn guess_without_isolated_unknowns() -> Result<()> {
+    // The opening leaves a 50/50 where both unknowns touch a number
+    let mut minefield = RustMinefield::from_layout(
+        2,
+        3,
+        vec![
+            false, false, //
+            false, false, //
+            true, false, //
+        ],
+    )?;
+
+    let mut solver = Solver::new(&mut minefield)?;
+    assert!(!solver.solve_without_guessing()?);
+    assert_eq!(solver.outcome(), None);
+    let outcome = solver.solve()?;
+    assert!((outcome.luck - 0.5).abs() < 0.001);
+    assert!((outcome.log_luck - 0.5f64.ln()).abs() < 0.001);
+
+    Ok(())
+}
+
+#[test]
+fn custom_presets() -> Result<()> {
+    let presets = load_presets("[tiny]\nwidth = 5\nheight = 4\nmines = 3\n")?;
+    let mode = Mode::Custom(vec!["tiny".into()]);
+    assert_eq!(
+        mode.preset(&presets)?,
+        Preset {
+            width: 5,
+            height: 4,
+            mines: 3
+        }
+    );
+    assert!(Mode::Custom(vec!["huge".into()]).preset(&presets).is_err());
+    assert!(load_presets("[full]\nwidth = 2\nheight = 2\nmines = 4\n").is_err());
+    assert!(load_presets("[expert]\nwidth = 5\nheight = 4\nmines = 3\n").is_err());
+
+    // Mode names round-trip
+    for mode in [Mode::Beginner, Mode::Intermediate, Mode::Expert, mode] {
+        assert_eq!(mode.to_string().parse::<Mode>()?, mode);
+    }
+    assert_eq!("expert".parse::<Mode>()?, Mode::Expert);
+
+    // Mine counts from a formula of the size
+    assert_eq!(Preset::with_formula(30, 16, "0.15*w*h + 5")?.mines, 77);
+    assert_eq!(Preset::with_formula(10, 10, "w - 10")?.mines, 0);
+    assert!(Preset::with_formula(10, 10, "w - 11").is_err());
+    assert!(Preset::with_formula(10, 10, "w*h").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn step_changes() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(
+        4,
+        4,
+        vec![
+            false, false, false, false, false, false, true, false, false, false, false, false,
+            true, false, false, true,
+        ],
+    )?;
+
+    let mut solver = Solver::new(&mut minefield)?;
+
+    let mut board = vec![Cell::Unknown; 16];
+    while solver.outcome().is_none() {
+        for (Pos(col, row), cell) in solver.step()? {
+            board[(col + row * 4) as usize] = cell;
+        }
+        let info = solver.info();
+        assert_eq!(info.mines_remaining, 3 - info.flags_placed);
+    }
+
+    // Replaying the changes gives the final board
+    assert_eq!(board, solver.board);
+    // Every opened cell was swept exactly once
+    let opened = board.iter().filter(|cell| **cell != Cell::Flag).count();
+    assert_eq!(solver.sweeps(), opened);
+    assert!(solver.step()?.is_empty());
+
+    // So does replaying the observed events of a solve
+    let mut solver = Solver::new(&mut minefield)?;
+    let mut observed = vec![Cell::Unknown; 16];
+    let mut observer = |event: &SolveEvent| {
+        let (Pos(col, row), cell) = match *event {
+            SolveEvent::Uncover { pos, cell } | SolveEvent::Guess { pos, cell, .. } => (pos, cell),
+            SolveEvent::Flag { pos } => (pos, Cell::Flag),
+        };
+        observed[(col + row * 4) as usize] = cell;
+    };
+    solver.solve_observed(Some(&mut observer))?;
+    assert_eq!(observed, board);
+
+    // The order of the deductions doesn't change where they end up
+    for deduce_order in [DeduceOrder::UncoverFirst, DeduceOrder::FlagFirst] {
+        let options = SolverOptions {
+            deduce_order,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        solver.solve()?;
+        assert_eq!(solver.board, board);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn logging_minefield() -> Result<()> {
+    let mut minefield =
+        RustMinefield::from_layout(3, 3, vec![false; 9])?.with_topology(&topology::Hex);
+    let mut logging = LoggingMinefield::new(&mut minefield);
+    assert_eq!(logging.topology().neighbors(Pos(1, 1)).len(), 6);
+
+    let mut solver = Solver::new(&mut logging)?;
+    solver.solve()?;
+    let sweeps = solver.sweeps();
+    let board = solver.board.clone();
+
+    // Every sweep of the solver is logged once, in order, with what it found
+    assert_eq!(logging.sweeps().len(), sweeps);
+    assert!(logging
+        .sweeps()
+        .windows(2)
+        .all(|pair| pair[0].at <= pair[1].at));
+    for sweep in logging.sweeps() {
+        let Pos(col, row) = sweep.pos;
+        assert_eq!(board[(col + row * 3) as usize], sweep.cell);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn known_safe() -> Result<()> {
+    // The default opening in the corner would hit the mine
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![true, false, false])?;
+    let mut solver =
+        Solver::with_known_safe(&mut minefield, SolverOptions::default(), &[Pos(2, 0)])?;
+    assert_eq!(solver.solve()?.status, Status::Won);
+    assert_eq!(
+        solver.moves()[0],
+        Move {
+            pos: Pos(2, 0),
+            cell: Cell::Number(0),
+            reason: Reason::KnownSafe
+        }
+    );
+    assert_eq!(solver.guesses(), 0);
+
+    // A safe cell is never a mine
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![true, false, false])?;
+    let mut solver = Solver::new(&mut minefield)?;
+    solver.mark_safe(Pos(0, 0))?;
+    assert!(matches!(
+        solver.reveal(Pos(0, 0), Cell::Flag),
+        Err(SolverError::KnownSafe { pos: Pos(0, 0) })
+    ));
+    assert_eq!(solver.solve()?.status, Status::Exploded { at: Pos(0, 0) });
+
+    // Nor flagged when a number says it is a mine
+    let mut minefield = RustMinefield::from_layout(2, 1, vec![true, false])?;
+    let mut solver = Solver::new(&mut minefield)?;
+    solver.reveal(Pos(1, 0), Cell::Number(1))?;
+    solver.mark_safe(Pos(0, 0))?;
+    assert!(matches!(
+        solver.solve(),
+        Err(SolverError::KnownSafe { pos: Pos(0, 0) })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn snapshot_restore() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(
+        3,
+        3,
+        vec![
+            false, false, false, //
+            false, false, false, //
+            false, false, true, //
+        ],
+    )?;
+
+    let mut solver = Solver::new(&mut minefield)?;
+    let start = solver.snapshot();
+
+    let outcome = solver.solve()?;
+    let end = solver.board.clone();
+
+    solver.restore(start);
+    assert!(solver.board.iter().all(|cell| *cell == Cell::Unknown));
+    assert_eq!(solver.outcome(), None);
+
+    assert_eq!(solver.solve()?, outcome);
+    assert_eq!(solver.board, end);
+
+    Ok(())
+}
+
+#[test]
+fn board_file() -> Result<()> {
+    let BoardFile {
+        mut minefield,
+        revealed,
+        ..
+    } = RustMinefield::parse("3 3 1\n.11\n.1F\n.11\n")?;
+
+    let mut solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+    solver.check_consistency()?;
+    assert_eq!(solver.solve()?.status, Status::Won);
+
+    // Zero next to a flag
+    let BoardFile {
+        mut minefield,
+        revealed,
+        ..
+    } = RustMinefield::parse("2 1 1\n0F\n")?;
+    let solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+    assert!(matches!(
+        solver.check_consistency(),
+        Err(SolverError::Inconsistent {
+            pos: Pos(0, 0),
+            number: 0,
+            mines: 1,
+            unknowns: 0
+        })
+    ));
+
+    assert!(RustMinefield::parse("").is_err());
+    assert!(RustMinefield::from_layout(2, 2, vec![false; 3]).is_err());
+    assert!(RustMinefield::from_layout(0, 2, Vec::new()).is_err());
+
+    // Several boards, with either separator
+    let boards = RustMinefield::parse_all("2 1 1\n1*\n\n1 1 0\n.\n---\n3 1 1\n.*.\n")?;
+    let sizes: Vec<(i32, i32)> = boards
+        .iter()
+        .map(|board| (board.minefield.width(), board.minefield.height()))
+        .collect();
+    assert_eq!(sizes, [(2, 1), (1, 1), (3, 1)]);
+    let error = RustMinefield::parse_all("1 1 0\n.\n---\n2 1 1\n..\n")
+        .err()
+        .unwrap();
+    assert!(format!("{:#}", error).starts_with("Board 2: "));
+    assert!(RustMinefield::parse_all("\n---\n").is_err());
+    assert!(RustMinefield::parse("2 2 1\n..\n..\n").is_err());
+    assert!(RustMinefield::parse("2 2 1\n.*\n").is_err());
+
+    // Two `1`s over the same two unknowns are a 50/50, unless a region hint says which one
+    // is safe
+    let BoardFile {
+        mut minefield,
+        revealed,
+        regions,
+    } = RustMinefield::parse("2 2 1\n11\n.*\nregion 0 0,1\n")?;
+    let mut solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+    for region in regions {
+        solver.add_region(region)?;
+    }
+    let outcome = solver.solve()?;
+    assert_eq!(outcome.status, Status::Won);
+    assert_eq!(outcome.luck, 1f32);
+    assert_eq!(solver.moves()[0].reason, Reason::Region { region: 0 });
+
+    // Hint that disagrees with the layout
+    assert!(RustMinefield::parse("2 2 1\n11\n.*\nregion 1 0,1\n").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn preopened_board() -> Result<()> {
+    // Only a `1` in the far corner is open, the first move has to be flagging its neighbor
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![false, true, false])?;
+    let revealed = [(Pos(2, 0), Cell::Number(1))];
+    let mut solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+
+    assert_eq!(
+        solver.step()?,
+        vec![(Pos(1, 0), Cell::Flag), (Pos(0, 0), Cell::Number(1))]
+    );
+    let outcome = solver.outcome().unwrap();
+    assert_eq!(outcome.status, Status::Won);
+    assert_eq!(outcome.luck, 1f32);
+
+    let reasons: Vec<Reason> = solver.moves().iter().map(|m| m.reason).collect();
+    assert_eq!(
+        reasons,
+        [Reason::OnlyMines { by: Pos(2, 0) }, Reason::AllMinesFlagged]
+    );
+    assert_eq!(
+        solver.moves()[0].to_string(),
+        "flagged (1,0) because cell (2,0) needed all remaining neighbors"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn show() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![false, true, false])?;
+    let revealed = [(Pos(2, 0), Cell::Number(1))];
+    let solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+
+    let ascii = RenderStyle::default();
+    let mut out = Vec::new();
+    solver.show(&mut out, &ascii)?;
+    assert_eq!(String::from_utf8(out)?, "Mines remaining: 1\n. . 1 \n");
+
+    let mut out = Vec::new();
+    solver.render(&mut out, Render::Unicode, &ascii)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        "Mines remaining: 1\n    0 1 2 \n  ┌───────┐\n0 │ . . 1 │\n  └───────┘\n"
+    );
+
+    let mut out = Vec::new();
+    solver.render(&mut out, Render::Unicode, &Glyphs::Emoji.style())?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        "Mines remaining: 1\n    0  1  2  \n  ┌──────────┐\n0 │ ⬜ ⬜ 1  │\n  └──────────┘\n"
+    );
+
+    let mut out = Vec::new();
+    solver.render_svg(&mut out)?;
+    let svg = String::from_utf8(out)?;
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<rect").count(), 3);
+    assert!(svg.contains(r##"<text x="60" y="18" fill="#0000ff">1</text>"##));
+
+    // The cell next to the 1 is the mine, so red, the other one green
+    let mut out = Vec::new();
+    solver.render(&mut out, Render::Heat, &ascii)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        format!(
+            "Mines remaining: 1\n{} {} 1 \n",
+            ".".truecolor(0, 255, 0),
+            ".".truecolor(255, 0, 0)
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn canonical_hash() -> Result<()> {
+    let corner = RustMinefield::parse("3 3 2\n**.\n...\n...\n")?.minefield;
+    let rotated = RustMinefield::parse("3 3 2\n..*\n..*\n...\n")?.minefield;
+    let other = RustMinefield::parse("3 3 2\n*.*\n...\n...\n")?.minefield;
+    assert_eq!(corner.canonical_hash(), rotated.canonical_hash());
+    assert_ne!(corner.canonical_hash(), other.canonical_hash());
+
+    // Mirrored, but not rotated, on a non-square board
+    let wide = RustMinefield::parse("3 2 1\n*..\n...\n")?.minefield;
+    let mirrored = RustMinefield::parse("3 2 1\n...\n..*\n")?.minefield;
+    assert_eq!(wide.canonical_hash(), mirrored.canonical_hash());
+
+    Ok(())
+}
+
+#[test]
+fn hex_board() -> Result<()> {
+    // On a hex grid the corner at (0, 0) only touches (1, 0) and (0, 1)
+    let minefield = RustMinefield::parse("3 3 1\n...\n...\n..*\n")?.minefield;
+    let mut minefield = minefield.with_topology(&topology::Hex);
+    assert_eq!(minefield.sweep_cell(0, 0)?, Cell::Number(0));
+    assert_eq!(minefield.sweep_cell(1, 1)?, Cell::Number(1));
+    assert_eq!(minefield.sweep_cell(2, 1)?, Cell::Number(1));
+    assert_eq!(minefield.sweep_cell(1, 2)?, Cell::Number(1));
+    assert_eq!(minefield.sweep_cell(2, 0)?, Cell::Number(0));
+
+    let mut solver = Solver::new(&mut minefield)?;
+    assert_eq!(solver.solve()?.status, Status::Won);
+
+    Ok(())
+}
+
+#[test]
+fn same_seed_same_game() -> Result<()> {
+    let preset = Mode::Intermediate.preset(&HashMap::new())?;
+    for seed in 0..3 {
+        let play = || -> Result<Vec<(Pos, Cell)>> {
+            let mut minefield = RustMinefield::with_seed(preset, seed);
+            let mut solver = Solver::new(&mut minefield)?;
+            let mut changes = Vec::new();
+            while solver.outcome().is_none() {
+                changes.extend(solver.step()?);
+            }
+            Ok(changes)
+        };
+        assert_eq!(play()?, play()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn huge_board() -> Result<()> {
+    assert!(Preset::new(100_000, 100_000, 10).is_err());
+
+    // Too many cells for the solver, but fails without allocating the board
+    let mut minefield = RustMinefield::new(Preset {
+        width: 65_536,
+        height: 65_536,
+        mines: 1,
+    });
+    assert!(Solver::new(&mut minefield).is_err());
+
+    // Fits in `i32`, but is over the cell limit
+    let mut minefield = RustMinefield::new(Preset::new(3000, 3000, 10)?);
+    assert!(matches!(
+        Solver::new(&mut minefield),
+        Err(SolverError::BoardTooLarge {
+            cells: 9_000_000,
+            ..
+        })
+    ));
+    assert!(minefield.sweep_cell(0, 0).is_err());
+    let mut minefield = RustMinefield::new(Preset::new(3, 3, 1)?).with_max_cells(8);
+    assert!(minefield.sweep_cell(0, 0).is_err());
+
+    // Indices past `i32::MAX` don't wrap around
+    assert_eq!(
+        cell_index(Pos(i32::MAX - 1, 1), i32::MAX, 2),
+        Some(2 * i32::MAX as usize - 1)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn seeded_rngs() -> Result<()> {
+    let preset = Mode::Beginner.preset(&HashMap::new())?;
+    for rng in [MineRng::Std, MineRng::Chacha, MineRng::Pcg] {
+        let layout = || -> Result<Vec<bool>> {
+            let mut minefield = RustMinefield::with_seed(preset, 7).with_rng(rng);
+            assert_ne!(minefield.sweep_cell(3, 4)?, Cell::Mine);
+            Ok(minefield.layout().to_vec())
+        };
+        let first = layout()?;
+        assert_eq!(first, layout()?);
+        assert_eq!(first.iter().filter(|mine| **mine).count(), 10);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn seed_strings() {
+    // Reference values of FNV-1a
+    assert_eq!(seed_from_str(""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(seed_from_str("a"), 0xaf63_dc4c_8601_ec8c);
+    assert_ne!(seed_from_str("hello"), seed_from_str("hellp"));
+}
+
+#[test]
+fn save_and_resume() -> Result<()> {
+    let preset = Mode::Beginner.preset(&HashMap::new())?;
+    let mut minefield = RustMinefield::with_seed(preset, 3);
+    let mut solver = Solver::new(&mut minefield)?;
+    solver.step()?;
+    solver.step()?;
+    let snapshot = solver.save()?;
+    let outcome = solver.solve()?;
+
+    let mut resumed = RustMinefield::from_saved(snapshot.layout.clone())?;
+    let mut solver = Solver::from_snapshot(&mut resumed, SolverOptions::default(), snapshot)?;
+    assert_eq!(solver.solve()?, outcome);
+
+    Ok(())
+}
+
+#[test]
+fn reconstruct_layout() -> Result<()> {
+    let preset = Mode::Beginner.preset(&HashMap::new())?;
+    for seed in 0..10 {
+        let mut minefield = RustMinefield::with_seed(preset, seed);
+        let mut solver = Solver::new(&mut minefield)?;
+        if solver.solve()?.status != Status::Won {
+            assert!(matches!(
+                solver.reconstruct_layout(),
+                Err(SolverError::LayoutUnknown)
+            ));
+            continue;
+        }
+        let reconstructed = solver.reconstruct_layout()?;
+        assert_eq!(reconstructed.to_board_file()?, minefield.to_board_file()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fifty_fifty() -> Result<()> {
+    // The mine is in the right column, the numbers left of it can't tell which cell
+    let mut minefield =
+        RustMinefield::from_layout(3, 2, vec![false, false, true, false, false, false])?;
+    let mut solver = Solver::new(&mut minefield)?;
+    assert!(!solver.solve_without_guessing()?);
+    assert_eq!(solver.fifty_fifty(), Some((Pos(2, 0), Pos(2, 1))));
+    assert!(minefield.has_fifty_fifty(SolverOptions::default())?);
+
+    // A third row opens up from below and settles it
+    let mut minefield = RustMinefield::from_layout(
+        3,
+        3,
+        vec![false, false, true, false, false, false, false, false, false],
+    )?;
+    assert!(!minefield.has_fifty_fifty(SolverOptions::default())?);
+
+    Ok(())
+}
+
+#[test]
+fn move_budget() -> Result<()> {
+    let preset = Mode::Intermediate.preset(&HashMap::new())?;
+    let mut minefield = RustMinefield::with_seed(preset, 0);
+    let mut solver = Solver::new(&mut minefield)?;
+    let full = solver.solve()?;
+    let full_moves = solver.moves().len();
+
+    for budget in [1, 7, 40] {
+        // Otherwise the game can end before the budget does
+        assert!(budget < full_moves);
+        let options = SolverOptions {
+            move_budget: Some(budget),
+            ..Default::default()
+        };
+        let mut minefield = RustMinefield::with_seed(preset, 0);
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        assert_eq!(solver.solve()?.status, Status::Incomplete);
+        assert_eq!(solver.moves().len(), budget);
+    }
+
+    // A budget the game doesn't reach changes nothing
+    let options = SolverOptions {
+        move_budget: Some(full_moves + 1),
+        ..Default::default()
+    };
+    let mut minefield = RustMinefield::with_seed(preset, 0);
+    assert_eq!(
+        Solver::with_options(&mut minefield, options)?.solve()?,
+        full
+    );
+
+    Ok(())
+}
+
+#[test]
+fn pos_order() {
+    let mut positions = vec![Pos(1, 1), Pos(2, 0), Pos(0, 1), Pos(0, 0)];
+    positions.sort();
+    assert_eq!(positions, [Pos(0, 0), Pos(2, 0), Pos(0, 1), Pos(1, 1)]);
+}
+
+#[test]
+fn first_guess() -> Result<()> {
+    assert_eq!(FirstGuess::Center.pos(30, 16, 99), Pos(15, 8));
+    assert_eq!(FirstGuess::auto(30, 16, 99), FirstGuess::Corner);
+
+    // Revealed numbers replace the first guess wherever it is
+    let mut minefield = RustMinefield::new(Preset::new(5, 5, 3)?);
+    let options = SolverOptions {
+        first_guess: FirstGuess::Center,
+        ..Default::default()
+    };
+    let solver = Solver::with_revealed(&mut minefield, options, &[(Pos(0, 0), Cell::Number(0))])?;
+    assert_eq!(solver.next, vec![Pos(0, 0)]);
+
+    Ok(())
+}
+
+/// Guess on a 1-2-1 over three unknowns, where the probabilities claim the outer cells are the
+/// safest while assuming either of them safe contradicts the numbers
+#[cfg(test)]
+fn lookahead_guess(guess_strategy: GuessStrategy, lookahead: bool) -> Result<Pos> {
+    let mut minefield =
+        RustMinefield::from_layout(3, 2, vec![false, false, false, true, false, true])?;
+    let revealed = [
+        (Pos(0, 0), Cell::Number(1)),
+        (Pos(1, 0), Cell::Number(2)),
+        (Pos(2, 0), Cell::Number(1)),
+    ];
+    let options = SolverOptions {
+        guess_strategy,
+        lookahead,
+        ..Default::default()
+    };
+    let solver = Solver::with_revealed(&mut minefield, options, &revealed)?;
+    let probabilities = GuessProbabilities {
+        border: HashMap::from([(Pos(0, 1), 0.1), (Pos(1, 1), 0.5), (Pos(2, 1), 0.1)]),
+        other: None,
+        relax_iterations: None,
+    };
+    Ok(solver.best_guess(&probabilities)?.0)
+}
+
+#[test]
+fn lookahead_safest() -> Result<()> {
+    assert_eq!(lookahead_guess(GuessStrategy::Safest, false)?, Pos(0, 1));
+    assert_eq!(lookahead_guess(GuessStrategy::Safest, true)?, Pos(1, 1));
+    Ok(())
+}
+
+#[test]
+fn lookahead_progress() -> Result<()> {
+    assert_ne!(lookahead_guess(GuessStrategy::Progress, false)?, Pos(1, 1));
+    assert_eq!(lookahead_guess(GuessStrategy::Progress, true)?, Pos(1, 1));
+    Ok(())
+}
+
+#[test]
+fn lookahead_cascade() -> Result<()> {
+    assert_ne!(lookahead_guess(GuessStrategy::Cascade, false)?, Pos(1, 1));
+    assert_eq!(lookahead_guess(GuessStrategy::Cascade, true)?, Pos(1, 1));
+    Ok(())
+}
+
+#[test]
+fn lookahead_biggest_region() -> Result<()> {
+    assert_ne!(
+        lookahead_guess(GuessStrategy::BiggestRegion, false)?,
+        Pos(1, 1)
+    );
+    assert_eq!(
+        lookahead_guess(GuessStrategy::BiggestRegion, true)?,
+        Pos(1, 1)
+    );
+    Ok(())
+}
+
+#[test]
+fn missed_deductions() -> Result<()> {
+    // A 1-2-1 over three unknowns decides all of them, but no single number does
+    let mut minefield =
+        RustMinefield::from_layout(3, 2, vec![false, false, false, true, false, true])?;
+    let revealed = [
+        (Pos(0, 0), Cell::Number(1)),
+        (Pos(1, 0), Cell::Number(2)),
+        (Pos(2, 0), Cell::Number(1)),
+    ];
+    let options = SolverOptions {
+        track_missed_deductions: true,
+        ..Default::default()
+    };
+    let mut solver = Solver::with_revealed(&mut minefield, options, &revealed)?;
+
+    // Assuming an outer cell is safe runs into a contradiction right away
+    let (constraints, remaining_mines) = (solver.constraints(), solver.mines_remaining());
+    assert!(contradicts_safe(&constraints, remaining_mines, Pos(0, 1)));
+    assert!(!contradicts_safe(&constraints, remaining_mines, Pos(1, 1)));
+    assert!(contradicts_safe(&constraints, remaining_mines, Pos(2, 1)));
+
+    solver.step()?;
+    assert_eq!(
+        solver.missed_deductions(),
+        [Pos(0, 1), Pos(1, 1), Pos(2, 1)]
+    );
+    assert_eq!(solver.solve()?.status, Status::Won);
+    assert_eq!(solver.missed_deductions().len(), 3);
+
+    // Without guessing the simple deductions stop right there
+    let mut minefield =
+        RustMinefield::from_layout(3, 2, vec![false, false, false, true, false, true])?;
+    let options = SolverOptions {
+        logic_only: true,
+        ..Default::default()
+    };
+    let mut solver = Solver::with_revealed(&mut minefield, options, &revealed)?;
+    assert_eq!(solver.solve()?.status, Status::Incomplete);
+    assert_eq!((solver.unknowns, solver.sweeps()), (3, 0));
+
+    // Strict mode takes the certain guesses, but not a real one
+    let strict = SolverOptions {
+        strict_no_guess: true,
+        ..Default::default()
+    };
+    let mut minefield =
+        RustMinefield::from_layout(3, 2, vec![false, false, false, true, false, true])?;
+    let mut solver = Solver::with_revealed(&mut minefield, strict, &revealed)?;
+    assert_eq!(solver.solve()?.status, Status::Won);
+    let mut minefield = RustMinefield::from_layout(2, 2, vec![false, true, true, false])?;
+    let mut solver = Solver::with_options(&mut minefield, strict)?;
+    assert!(matches!(
+        solver.solve(),
+        Err(SolverError::GuessRequired { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn constraints_without_board() {
+    // A `1` on two unknowns, one of which also needs a mine for a `1` next to it
+    let constraints = [
+        Constraint {
+            unknowns: vec![Pos(0, 0), Pos(1, 0)],
+            mines_remaining: 1,
+        },
+        Constraint {
+            unknowns: vec![Pos(1, 0)],
+            mines_remaining: 1,
+        },
+    ];
+    assert_eq!(constraints[0].decided(), None);
+    assert_eq!(constraints[1].decided(), Some(true));
+    assert_eq!(constraints[0].to_string(), "1 mines among (0,0) (1,0)");
+
+    let (probs, _) = relax(&constraints, 1, 2);
+    assert!(probs[&Pos(0, 0)] < 0.001);
+    assert!((probs[&Pos(1, 0)] - 1f32).abs() < 0.001);
+}
+
+#[test]
+fn relax_conserves_mines() {
+    let constraint = |unknowns: &[(i32, i32)], mines_remaining| Constraint {
+        unknowns: unknowns.iter().map(|(col, row)| Pos(*col, *row)).collect(),
+        mines_remaining,
+    };
+
+    // Separate numbers, a `1` on three unknowns and a `2` on four, leave 3 mines on the border
+    let constraints = [
+        constraint(&[(0, 0), (1, 0), (2, 0)], 1),
+        constraint(&[(0, 2), (1, 2), (2, 2), (3, 2)], 2),
+    ];
+    let (probs, iterations) = relax(&constraints, 10, 40);
+    assert!(iterations < RELAX_ITERATIONS);
+    assert!((ordered_sum(&probs) - 3f32).abs() < 0.001);
+
+    // Two `1`s sharing a cell, with a single mine left the global correction has to bring
+    // the border down to it
+    let constraints = [
+        constraint(&[(0, 0), (1, 0)], 1),
+        constraint(&[(1, 0), (2, 0)], 1),
+    ];
+    let (probs, iterations) = relax(&constraints, 1, 3);
+    assert!(iterations < RELAX_ITERATIONS);
+    assert!((ordered_sum(&probs) - 1f32).abs() < 0.001);
+    assert!((probs[&Pos(1, 0)] - 1f32).abs() < 0.001);
+}
+
+#[test]
+fn player_moves() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(4, 1, vec![false, false, false, true])?;
+    let mut solver = Solver::new(&mut minefield)?;
+
+    // The `0` opens up to the `1` next to the mine
+    assert_eq!(solver.sweep(Pos(0, 0))?, Cell::Number(0));
+    assert_eq!(solver.unknowns, 1);
+    assert_eq!(solver.outcome().unwrap().status, Status::Won);
+    assert!(matches!(
+        solver.sweep(Pos(3, 0)),
+        Err(SolverError::GameOver)
+    ));
+
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![false, true, false])?;
+    let mut solver = Solver::new(&mut minefield)?;
+    solver.sweep(Pos(2, 0))?;
+    // The `1` needs the middle cell, so the far end is safe
+    assert_eq!(solver.hint()?, (Pos(0, 0), 0f32));
+    solver.toggle_flag(Pos(1, 0))?;
+    assert_eq!(solver.mines_remaining(), 0);
+    solver.toggle_flag(Pos(1, 0))?;
+    assert_eq!(solver.mines_remaining(), 1);
+    assert_eq!(solver.sweep(Pos(1, 0))?, Cell::Mine);
+    assert_eq!(
+        solver.outcome().unwrap().status,
+        Status::Exploded { at: Pos(1, 0) }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn dump_board() -> Result<()> {
+    let mut minefield = RustMinefield::with_seed(Preset::new(5, 3, 4)?, 3);
+    assert!(minefield.to_board_file().is_err());
+    minefield.generate(Pos(2, 1))?;
+    assert!(!minefield.layout()[7]);
+
+    let board = minefield.to_board_file()?;
+    assert!(board.starts_with("5 3 4\n"));
+    let parsed = RustMinefield::parse(&board)?;
+    assert_eq!(parsed.minefield.layout(), minefield.layout());
+    assert!(parsed.revealed.is_empty());
+
+    let python = minefield.to_python()?;
+    assert!(python.starts_with("{\n    \"width\": 5,\n    \"height\": 3,\n"));
+    let rows: Vec<&str> = board.lines().skip(1).collect();
+    assert!(python.contains(&format!(
+        "        \"{}\",\n        \"{}\",",
+        rows[0], rows[1]
+    )));
+    assert!(python.ends_with("    ],\n}\n"));
+
+    Ok(())
+}
+
+#[test]
+fn neighbor_counts() -> Result<()> {
+    let (width, height) = (9, 7);
+    let mut minefield = RustMinefield::with_seed(Preset::new(width, height, 20)?, 11);
+    minefield.generate(Pos(4, 3))?;
+    let layout = minefield.layout().to_vec();
+    let mine = |col: i32, row: i32| {
+        (0..width).contains(&col)
+            && (0..height).contains(&row)
+            && layout[usize::try_from(row * width + col).unwrap()]
+    };
+
+    for row in 0..height {
+        for col in 0..width {
+            if mine(col, row) {
+                continue;
+            }
+            let mut count = 0;
+            for dr in -1..=1 {
+                for dc in -1..=1 {
+                    if (dc, dr) != (0, 0) && mine(col + dc, row + dr) {
+                        count += 1;
+                    }
+                }
+            }
+            assert_eq!(minefield.sweep_cell(col, row)?, Cell::Number(count));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn three_bv() -> Result<()> {
+    // One opening reaches every number around the mine in the corner
+    let mut minefield = RustMinefield::parse("5 3 1\n....*\n.....\n.....\n")?.minefield;
+    assert_eq!(minefield.three_bv(), Some(1));
+
+    // Five numbers right of the mine are out of reach of the opening on the left
+    let other = RustMinefield::parse("5 3 1\n.....\n...*.\n.....\n")?.minefield;
+    assert_eq!(other.three_bv(), Some(6));
+
+    let mut solver = Solver::new(&mut minefield)?;
+    assert_eq!(solver.solve()?.status, Status::Won);
+    assert_eq!(solver.clicks(), 1);
+    assert!(solver.sweeps() > 1);
+
+    Ok(())
+}
+
+#[test]
+fn compat_layouts() -> Result<()> {
+    let preset = Preset::new(5, 4, 5)?;
+    let mines = |compat| -> Result<Vec<usize>> {
+        let mut minefield = RustMinefield::with_seed(preset, 42).with_compat(compat);
+        minefield.generate(Pos(0, 0))?;
+        Ok((0..20).filter(|i| minefield.layout()[*i]).collect())
+    };
+
+    // Computed from the documented schemes, independent of this implementation
+    assert_eq!(mines(Compat::Splitmix)?, [6, 7, 9, 15, 19]);
+    assert_eq!(mines(Compat::Msvc)?, [1, 4, 9, 14, 18]);
+
+    // Columns past `MSVC_MAX_SIDE` are out of reach of `rand()`
+    let wide = |width| {
+        let preset = Preset::new(width, 2, width)?;
+        RustMinefield::with_seed(preset, 42)
+            .with_compat(Compat::Msvc)
+            .generate(Pos(0, 0))
+    };
+    assert!(wide(MSVC_MAX_SIDE).is_ok());
+    let error = wide(MSVC_MAX_SIDE + 1).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SolverError>(),
+        Some(SolverError::MsvcBoardTooLarge { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn only_mines_left() -> Result<()> {
+    // The `1` finds the first mine, nothing touches the second one
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![false, true, true])?;
+    let mut solver = Solver::new(&mut minefield)?;
+
+    let outcome = solver.solve()?;
+    assert_eq!(outcome.status, Status::Won);
+    assert_eq!(outcome.luck, 1f32);
+    let last = solver.moves().last().unwrap();
+    assert_eq!((last.pos, last.reason), (Pos(2, 0), Reason::OnlyMinesLeft));
+
+    Ok(())
+}
+
+#[test]
+fn suggest_matches_step() -> Result<()> {
+    for seed in 0..20 {
+        let mut minefield = RustMinefield::with_seed(Preset::new(16, 16, 40)?, seed);
+        let mut solver = Solver::new(&mut minefield)?;
+        while solver.outcome().is_none() {
+            let suggestion = solver.suggest()?;
+            let seen = solver.moves().len();
+            solver.step()?;
+            let m = solver.moves()[seen];
+            let expected = match (m.cell, m.reason) {
+                (_, Reason::Guess { p }) => Suggestion::Guess { pos: m.pos, p },
+                (Cell::Flag, reason) => Suggestion::Mine { pos: m.pos, reason },
+                (_, reason) => Suggestion::Safe { pos: m.pos, reason },
+            };
+            assert_eq!(suggestion, expected);
+        }
+        assert!(matches!(solver.suggest(), Err(SolverError::GameOver)));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sweep_orders() -> Result<()> {
+    assert_eq!(
+        SweepOrder::Spiral.positions(3, 3),
+        vec![
+            Pos(0, 0),
+            Pos(1, 0),
+            Pos(2, 0),
+            Pos(2, 1),
+            Pos(2, 2),
+            Pos(1, 2),
+            Pos(0, 2),
+            Pos(0, 1),
+            Pos(1, 1)
+        ]
+    );
+    // Every cell once, also on boards that end in a single row or column
+    for (width, height) in [(4, 2), (2, 5), (5, 3), (1, 1)] {
+        let mut spiral = SweepOrder::Spiral.positions(width, height);
+        spiral.sort();
+        assert_eq!(spiral, SweepOrder::RowMajor.positions(width, height));
+    }
+
+    // Without mines everything is revealed right away, in the chosen order
+    for order in [
+        SweepOrder::ColumnMajor,
+        SweepOrder::RowMajor,
+        SweepOrder::Spiral,
+    ] {
+        let mut minefield = RustMinefield::from_layout(3, 2, vec![false; 6])?;
+        let options = SolverOptions {
+            sweep_order: order,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        assert_eq!(solver.solve()?.status, Status::Won);
+        let opened: Vec<Pos> = solver.moves().iter().map(|m| m.pos).collect();
+        let expected = order.positions(3, 2);
+        assert_eq!(opened, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn board_state_rows() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(3, 2, [false, true, false].repeat(2))?;
+    let revealed = [(Pos(0, 0), Cell::Number(2)), (Pos(1, 1), Cell::Flag)];
+    let solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+
+    // What serde sees
+    let state = solver.board_state();
+    let rows = BoardRows::from(state.clone());
+    assert_eq!(rows.rows, vec!["2..", ".F."]);
+    let back = BoardState::try_from(rows)?;
+    assert_eq!(back, state);
+    assert_eq!(back.revealed(), revealed);
+
+    let rows = |width, height, rows: &[&str]| BoardRows {
+        width,
+        height,
+        rows: rows.iter().map(|row| row.to_string()).collect(),
+    };
+    assert!(BoardState::try_from(rows(2, 1, &["2x"])).is_err());
+    assert!(BoardState::try_from(rows(3, 1, &["2."])).is_err());
+    assert!(BoardState::try_from(rows(2, 2, &["2."])).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn zero_mines() -> Result<()> {
+    let preset = Preset::with_formula(5, 4, "w - 5")?;
+    assert_eq!(preset.mines, 0);
+
+    // Everything is safe from the start, so the first pass opens the whole board
+    let mut minefield = RustMinefield::with_seed(preset, 7);
+    let mut solver = Solver::new(&mut minefield)?;
+    assert!(matches!(solver.suggest()?, Suggestion::Safe { .. }));
+    assert_eq!(solver.win_probability(64)?, 1f64);
+    let outcome = solver.solve()?;
+    assert_eq!(outcome.status, Status::Won);
+    assert_eq!(outcome.luck, 1f32);
+    assert_eq!(solver.guesses(), 0);
+    assert!(solver.solved());
+    assert!(solver.moves().iter().all(|m| m.cell == Cell::Number(0)));
+    assert_eq!(solver.three_bv(), Some(1));
+    assert_eq!(solver.clicks(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn certain_cells() -> Result<()> {
+    // The `0`s at both ends clear their neighbors, which leaves the middle for the mine
+    let mut minefield = RustMinefield::from_layout(5, 1, vec![false, false, true, false, false])?;
+    let revealed = [(Pos(0, 0), Cell::Number(0)), (Pos(4, 0), Cell::Number(0))];
+    let solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+    assert_eq!(solver.safe_cells(), Some(vec![Pos(1, 0), Pos(3, 0)]));
+    assert_eq!(solver.mine_cells(), Some(vec![Pos(2, 0)]));
+
+    let options = SolverOptions {
+        csp_node_limit: 0,
+        ..Default::default()
+    };
+    let solver = Solver::with_revealed(&mut minefield, options, &revealed)?;
+    assert_eq!(solver.safe_cells(), None);
+
+    Ok(())
+}
+
+#[test]
+fn parallel_games_deterministic() -> Result<()> {
+    let preset = Preset::new(9, 9, 10)?;
+    let play = |game: usize| {
+        let mut minefield = RustMinefield::with_seed(preset, 1000 + game as u64);
+        let mut solver = Solver::new(&mut minefield).unwrap();
+        solver.solve().unwrap()
+    };
+    let sequential: Vec<Outcome> = (0..40).map(play).collect();
+    for threads in [1, 3, 8, 100] {
+        assert_eq!(parallel_games(40, threads, play), sequential);
+    }
+    assert!(parallel_games(0, 4, play).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn hidden_numbers() -> Result<()> {
+    // A `0` in the corner would clear the middle, a hidden one says nothing
+    let BoardFile {
+        mut minefield,
+        revealed,
+        ..
+    } = RustMinefield::parse("3 1 1\n?.*\n")?;
+    assert_eq!(revealed, vec![(Pos(0, 0), Cell::Hidden)]);
+    let mut solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+    assert!(solver.active_constraints().is_empty());
+    assert_eq!(solver.safe_cells(), Some(vec![]));
+    assert_eq!(
+        solver.suggest()?,
+        Suggestion::Guess {
+            pos: Pos(1, 0),
+            p: 0.5
+        }
+    );
+    assert!((solver.win_probability(64)? - 0.5).abs() < 1e-9);
+    assert_eq!(BoardRows::from(solver.board_state()).rows, vec!["?.."]);
+
+    // Still counts as opened, the game is won once the middle is open
+    assert_eq!(solver.sweep(Pos(1, 0))?, Cell::Number(1));
+    assert_eq!(solver.outcome().unwrap().status, Status::Won);
+    assert_eq!(solver.reconstruct_layout()?.layout(), &[false, false, true]);
+
+    Ok(())
+}
+
+#[test]
+fn load_bearing_flags() -> Result<()> {
+    // The mine at (3,0) lets the 1 at (2,0) open (1,0), the one at (0,0) enables nothing
+    let layout = vec![true, false, false, true, false];
+    let revealed = [(Pos(2, 0), Cell::Number(1)), (Pos(4, 0), Cell::Number(1))];
+    for (flagging, clicks) in [(Flagging::All, 2), (Flagging::LoadBearing, 1)] {
+        let mut minefield = RustMinefield::from_layout(5, 1, layout.clone())?;
+        let options = SolverOptions {
+            flagging,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_revealed(&mut minefield, options, &revealed)?;
+        assert_eq!(solver.solve()?.status, Status::Won);
+        let flagged: Vec<Pos> = solver
+            .moves()
+            .iter()
+            .filter(|m| m.cell == Cell::Flag)
+            .map(|m| m.pos)
+            .collect();
+        // Every flag is a move either way, only the clicks differ
+        assert_eq!(flagged, vec![Pos(3, 0), Pos(0, 0)]);
+        assert_eq!(solver.flag_clicks(), clicks);
+        assert_eq!(solver.guesses(), 0);
+    }
+    Ok(())
+}
+
+#[test]
+fn no_cell_off_border() -> Result<()> {
+    let mut minefield = RustMinefield::from_layout(3, 1, vec![false, false, true])?;
+    let revealed = [(Pos(0, 0), Cell::Number(0))];
+    let solver = Solver::with_revealed(&mut minefield, SolverOptions::default(), &revealed)?;
+
+    // Probabilities that claim a cell off the border when every unknown is on it
+    let probabilities = GuessProbabilities {
+        border: HashMap::from([(Pos(1, 0), 0.5), (Pos(2, 0), 0.5)]),
+        other: Some(0.1),
+        relax_iterations: None,
+    };
+    assert!(matches!(
+        solver.best_guess(&probabilities),
+        Err(SolverError::NoCellOffBorder)
+    ));
+    Ok(())
+}
+
+#[test]
+fn random_guesses() -> Result<()> {
+    let preset = Preset::new(9, 9, 10)?;
+    let play = |guess_seed| -> Result<Vec<Move>> {
+        let mut minefield = RustMinefield::with_seed(preset, 3);
+        let options = SolverOptions {
+            guess_strategy: GuessStrategy::Random,
+            guess_seed,
+            ..Default::default()
+        };
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        solver.solve()?;
+        Ok(solver.moves().to_vec())
+    };
+    assert_eq!(play(7)?, play(7)?);
+    Ok(())
+}
+
+#[test]
+fn generate_with_mask() -> Result<()> {
+    // A free 3x3 block in the top left corner, the rest is all mines
+    let forbidden: HashSet<Pos> = (0..3)
+        .flat_map(|col| (0..3).map(move |row| Pos(col, row)))
+        .collect();
+    for compat in [Compat::Rejection, Compat::Splitmix, Compat::Msvc] {
+        let mut minefield = RustMinefield::with_seed(Preset::new(4, 4, 7)?, 1).with_compat(compat);
+        minefield.generate_with_mask(&forbidden)?;
+        assert_eq!(minefield.sweep_cell(0, 0)?, Cell::Number(0));
+        assert_eq!(minefield.sweep_cell(2, 2)?, Cell::Number(5));
+        assert_eq!(minefield.sweep_cell(3, 3)?, Cell::Mine);
+    }
+
+    let mut minefield = RustMinefield::new(Preset::new(4, 4, 8)?);
+    assert!(minefield.generate_with_mask(&forbidden).is_err());
+    let off_board = HashSet::from([Pos(4, 0)]);
+    assert!(minefield.generate_with_mask(&off_board).is_err());
+    Ok(())
+}
@@ -1,592 +1,1586 @@
-use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, Context, Result};
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand, ValueEnum};
 use owo_colors::OwoColorize;
-use pyo3::{prelude::*, types::PyDict};
-use rand::{thread_rng, Rng};
-use std::collections::HashMap;
-
-const SOURCE: &str = include_str!("../lib/decode_demcon3/mineField.py");
-
-const NEIGHBORS: [(i32, i32); 8] = [
-    (1, 1),
-    (1, 0),
-    (1, -1),
-    (0, 1),
-    (0, -1),
-    (-1, 1),
-    (-1, 0),
-    (-1, -1),
-];
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use rusty_mines::MinefieldBuilder;
+use rusty_mines::{
+    load_presets, parallel_games, seed_from_str,
+    stats::{percentile, wilson_interval, Aggregate, Histogram, Welford},
+    topology::{Hex, Square, Topology},
+    BoardFile, BoardInfo, Cell, Compat, DeduceOrder, FirstGuess, Flagging, Glyphs, GuessStrategy,
+    LoggingMinefield, MineRng, Minefield, Mode, Move, Outcome, Pos, Preset, Reason, Region, Render,
+    RenderStyle, RustMinefield, Solver, SolverOptions, SolverSnapshot, Status, Suggestion,
+    SweepOrder, Timings, RELAX_ITERATIONS,
+};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+mod tui;
 
-#[derive(Subcommand, Copy, Clone, Debug, PartialEq, Eq, Hash)]
-enum Mode {
-    Beginner,
-    Intermediate,
-    Expert,
-}
+#[derive(Parser)]
+#[clap(about, long_about = None)]
+struct Cli {
+    /// Only optional for `--examples`, `main` checks it
+    #[clap(subcommand)]
+    command: Option<Command>,
 
-struct MinefieldBuilder<'a> {
-    class: &'a PyAny,
-    presets: HashMap<Mode, (i32, i32, i32, &'a PyDict)>,
-}
+    /// Print example commands for the common tasks and exit
+    #[clap(long, value_parser)]
+    examples: bool,
 
-impl<'a> MinefieldBuilder<'a> {
-    fn new(py: Python<'a>) -> Result<Self> {
-        let module = PyModule::from_code(py, SOURCE, "mineField", "mineField")?;
-        let class = module.getattr("MineField")?;
+    #[clap(short, long, value_parser)]
+    iterations: Option<usize>,
 
-        let list = [
-            (Mode::Beginner, "BEGINNER_FIELD"),
-            (Mode::Intermediate, "INTERMEDIATE_FIELD"),
-            (Mode::Expert, "EXPERT_FIELD"),
-        ];
+    /// Use the Rust minefield instead of the Python one, always on when built without the
+    /// `python` feature
+    #[clap(short, long, value_parser)]
+    native: bool,
 
-        let presets = list
-            .iter()
-            .map(|(mode, name)| {
-                let kwargs = module
-                    .getattr(name)?
-                    .downcast::<PyDict>()
-                    .map_err(|e| anyhow!("{}", e))?;
+    /// TOML file with named presets, selectable as mode
+    #[clap(long, value_parser)]
+    presets: Option<PathBuf>,
+
+    /// Replay native boards on a fresh minefield with the same layout, fails on differing moves.
+    /// Uses the minefield and solver options of a native batch, game `n` gets seed `seed + n`
+    #[clap(long, value_parser)]
+    compare_backends: bool,
+
+    /// Search nodes the exact probability solver may use per guess, 0 to always approximate
+    #[clap(long, value_parser, default_value = "100000")]
+    csp_node_limit: usize,
+
+    /// How to pick a cell when no cell is certainly safe
+    #[clap(long, value_enum, default_value = "safest")]
+    guess_strategy: GuessStrategy,
+
+    /// What to do when the deductions are stuck, `random` and `logic-only` are baselines for
+    /// the win rate of `smart`
+    #[clap(long, value_enum, default_value = "smart")]
+    solver: SolverKind,
+
+    /// Cell to sweep first, `auto` picks the best one for the built-in modes
+    #[clap(long, value_enum, default_value = "auto")]
+    first: FirstGuess,
+
+    /// Skip guesses that turn out impossible when assumed safe, one step of deductions ahead
+    #[clap(long, value_parser)]
+    lookahead: bool,
+
+    /// Order of the deductions within a pass
+    #[clap(long, value_enum, default_value = "mixed")]
+    deduce_order: DeduceOrder,
+
+    /// Order of the final reveal, once every mine is flagged
+    #[clap(long, value_enum, default_value = "column-major")]
+    sweep_order: SweepOrder,
+
+    /// Which flags cost a click for `--efficiency`, `load-bearing` skips the ones no number
+    /// needs to open its other neighbors
+    #[clap(long, value_enum, default_value = "all")]
+    flagging: Flagging,
+
+    /// Never guess, games where the deductions get stuck end incomplete
+    #[clap(long, value_parser)]
+    logic_only: bool,
+
+    /// Fail instead of making a guess that isn't certainly safe, for pipelines that only accept
+    /// boards that need no luck
+    #[clap(long, value_parser)]
+    assume_no_guess_needed: bool,
+
+    /// Stop every game as incomplete after this many opened or flagged cells, and report how
+    /// much of the board got opened
+    #[clap(long, value_parser)]
+    budget_moves: Option<usize>,
+
+    /// Print a histogram of the luck of the won games of a batch
+    #[clap(long, value_parser)]
+    luck_histogram: bool,
+
+    /// Print how often the safe cells that the solver opened in a batch show each number
+    #[clap(long, value_parser, requires = "iterations")]
+    number_stats: bool,
+
+    /// Also report how many boards of a batch can be solved without guessing
+    #[clap(long, value_parser)]
+    measure_fairness: bool,
+
+    /// Report the cells the exact solver could decide when the deductions got stuck
+    #[clap(long, value_parser)]
+    missed_deductions: bool,
+
+    /// Report how many iterations the probability relaxation needed per guess
+    #[clap(long, value_parser)]
+    relax_stats: bool,
+
+    /// Compare the sweeps of won games with flood fill, as clicks, to the 3BV of the board.
+    /// Only native minefields and board files know their 3BV.
+    #[clap(long, value_parser)]
+    efficiency: bool,
+
+    /// Print the time spent on deductions and on guessing
+    #[clap(long, value_parser)]
+    timings: bool,
+
+    /// Print the constraints of the revealed numbers and region hints of a single game every
+    /// time the deductions get stuck, before the guess
+    #[clap(long, value_parser, conflicts_with_all = &["iterations", "steps"])]
+    dump_constraints: bool,
+
+    /// Print the number and sizes of the independent groups of unknown cells along the border
+    /// of a single game every time the deductions get stuck, before the guess
+    #[clap(long, value_parser, conflicts_with_all = &["iterations", "steps"])]
+    dump_components: bool,
+
+    /// List every move of a single game with the reason for it
+    #[clap(long, value_parser)]
+    explain: bool,
+
+    /// Play a single game yourself, the solver gives hints on request
+    #[clap(long, value_parser, conflicts_with = "iterations")]
+    interactive: bool,
+
+    /// Solve fresh boards until one is won, then show that one
+    #[clap(long, value_parser, conflicts_with_all = &["iterations", "interactive", "steps", "load", "file"])]
+    repeat_until_win: bool,
+
+    /// Generate seeded boards from `--seed` on until one solves without guessing from a center
+    /// opening, then print its seed and the board as board file
+    #[clap(long, value_parser, conflicts_with_all = &["iterations", "interactive", "repeat-until-win", "load", "file", "dump-board", "emit-python"])]
+    first_safe_center: bool,
+
+    /// Solve seeded native boards from `--seed` on until the solver panics, then print the
+    /// seed of that board. Runs until stopped otherwise.
+    #[clap(long, value_parser, conflicts_with_all = &["iterations", "interactive", "repeat-until-win", "load", "file", "first-safe-center", "threads"])]
+    stress: bool,
+
+    /// Regenerate native boards that run into a 50/50, two cells that no sweep can tell apart
+    #[clap(long, value_parser)]
+    no_fifty_fifty: bool,
+
+    /// Boards `--repeat-until-win`, `--first-safe-center` and `--no-fifty-fifty` try before
+    /// giving up
+    #[clap(long, value_parser, default_value = "1000")]
+    max_attempts: usize,
+
+    /// Celebrate a won single game, or point out the mine that ended it
+    #[clap(long, value_parser)]
+    fun: bool,
+
+    /// Generator for the mine placement of native minefields
+    #[clap(long, value_enum, default_value = "std")]
+    rng: MineRng,
+
+    /// Seed to layout mapping of native minefields, to reproduce boards of other engines
+    #[clap(long, value_enum, default_value = "rejection")]
+    compat: Compat,
+
+    /// How to draw the board
+    #[clap(long, value_enum, default_value = "color")]
+    render: Render,
+
+    /// Characters for flags, mines and unknown cells
+    #[clap(long, value_enum, default_value = "ascii")]
+    glyphs: Glyphs,
+
+    /// Grid of native minefields and board files
+    #[clap(long, value_enum, default_value = "square")]
+    topology: GridTopology,
+
+    /// Seed for native mine layouts, game `n` of a batch uses `seed + n`
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// Seed from a name instead of a number, to share boards by name. The same name gives the
+    /// same board on every machine, with `--rng chacha` also across `rand` versions.
+    #[clap(long, value_parser, conflicts_with = "seed")]
+    seed_string: Option<String>,
+
+    /// Only report through the exit code: 0 won, 1 exploded, 2 incomplete
+    #[clap(short, long, value_parser)]
+    quiet: bool,
+
+    /// Play the games of a seeded native batch on this many threads. Game `n` always gets
+    /// seed `seed + n`, so the results are the same for any number of threads.
+    #[clap(long, value_parser, requires_all = &["iterations", "seed"], conflicts_with_all = &["no-fifty-fifty", "mines-range", "optimality-gap"])]
+    threads: Option<usize>,
+
+    /// Count games of a batch that fail with an error, like a hiccup of the Python backend,
+    /// as errored and continue with the next game, instead of stopping the batch
+    #[clap(long, value_parser, requires = "iterations")]
+    keep_going: bool,
+
+    /// Print one CSV line per game in batch mode, also makes the luck percentiles exact
+    #[clap(long, value_parser)]
+    csv: bool,
+
+    /// Board width, overrides the mode
+    #[clap(long, value_parser)]
+    width: Option<i32>,
+
+    /// Board height, overrides the mode
+    #[clap(long, value_parser)]
+    height: Option<i32>,
+
+    /// Number of mines, overrides the mode
+    #[clap(long, value_parser, conflicts_with = "density")]
+    mines: Option<i32>,
+
+    /// Play `--iterations` games for each mine count in `FROM..TO`, or `FROM..=TO` to include
+    /// `TO`, and print the win rate per count
+    #[clap(long, value_parser = parse_mines_range, requires = "iterations", conflicts_with_all = &["mines", "density"])]
+    mines_range: Option<Range<i32>>,
+
+    /// Rate every guess of `--iterations` games against the optimal guess found by searching
+    /// the whole game tree, on boards of at most this many cells. The search is exponential,
+    /// 16 cells take a fraction of a second per game, 20 cells tens of seconds.
+    #[clap(
+        long,
+        value_parser,
+        value_name = "MAX_CELLS",
+        requires = "iterations",
+        conflicts_with = "mines-range"
+    )]
+    optimality_gap: Option<usize>,
+
+    /// Mine count as formula of the board width `w` and height `h`, like `0.15*w*h + 5`, with
+    /// `+ - * /` and parentheses. Rounded to the nearest count.
+    #[clap(long, value_parser, conflicts_with_all = &["mines", "density", "mines-range"])]
+    mines_formula: Option<String>,
+
+    /// Fraction of the cells that are mines, instead of a fixed count
+    #[clap(long, value_parser)]
+    density: Option<f32>,
+
+    /// Biggest board to allocate, bigger boards are an error
+    #[clap(long, value_parser, default_value = "4000000")]
+    max_board_cells: usize,
+
+    /// Print the mine layout of a native board as board file instead of solving it, to edit
+    /// it or keep it as fixture. Uses `--seed` and keeps the `--first` cell free.
+    #[clap(long, value_parser)]
+    dump_board: bool,
+
+    /// Like `--dump-board`, but as Python dict with the arguments of `MineField` from the
+    /// challenge and the layout
+    #[clap(long, value_parser, conflicts_with = "dump-board")]
+    emit_python: bool,
+
+    /// Save a single game as JSON when it stops, to resume it later with `--load`. Only for
+    /// native minefields and board files.
+    #[clap(long, value_parser)]
+    save: Option<PathBuf>,
+
+    /// Write the mine layout of a won single game as board file, also for Python minefields,
+    /// to replay the game natively
+    #[clap(long, value_parser)]
+    export_layout: Option<PathBuf>,
+
+    /// Write the board of a single game when it stops, as JSON, SVG image or plain text by
+    /// the extension: `.json`, `.svg` or `.txt`
+    #[clap(long, value_parser)]
+    out: Option<PathBuf>,
+
+    /// Resume a game saved with `--save`
+    #[clap(long, value_parser)]
+    load: Option<PathBuf>,
+
+    /// Pause a single game after this many solver steps
+    #[clap(long, value_parser)]
+    steps: Option<usize>,
+
+    /// Solve the board from a board file instead of a random one, `-` reads it from stdin.
+    /// A file with several boards, separated by `---` lines, solves them all.
+    #[clap(long, value_parser)]
+    file: Option<PathBuf>,
+}
 
-                let width: i32 = PyAny::get_item(kwargs, "width")?.extract()?;
-                let height: i32 = PyAny::get_item(kwargs, "height")?.extract()?;
-                let number_of_mines: i32 = PyAny::get_item(kwargs, "number_of_mines")?.extract()?;
+fn record(minefield: &mut impl Minefield, options: SolverOptions) -> Result<Vec<(Pos, Cell)>> {
+    let mut logging = LoggingMinefield::new(minefield);
+    Solver::with_options(&mut logging, options)?.solve()?;
+    Ok(logging
+        .into_sweeps()
+        .into_iter()
+        .map(|sweep| (sweep.pos, sweep.cell))
+        .collect())
+}
 
-                Ok((*mode, (width, height, number_of_mines, kwargs)))
-            })
-            .collect::<Result<HashMap<Mode, (i32, i32, i32, &PyDict)>>>()?;
+/// Game `n` uses seed `seed + n` like a native batch, with the same minefield and solver
+/// options, so a discrepancy can be replayed with `--seed`
+fn compare_backends(cli: &Cli, preset: Preset, iterations: usize) -> Result<()> {
+    let options = cli.solver_options();
+    let base = cli.seed.unwrap_or_else(rand::random);
+    let mut discrepancies = 0;
+    for game in 0..iterations {
+        let seed = base.wrapping_add(game as u64);
+        let mut minefield = RustMinefield::with_seed(preset, seed)
+            .with_topology(cli.topology.topology())
+            .with_rng(cli.rng)
+            .with_compat(cli.compat)
+            .with_max_cells(cli.max_board_cells);
+        let sweeps = record(&mut minefield, options)?;
+
+        let mut replay =
+            RustMinefield::from_layout(preset.width, preset.height, minefield.layout().to_vec())?
+                .with_topology(cli.topology.topology());
+        let replayed = record(&mut replay, options)?;
+        for (step, (expected, actual)) in sweeps.iter().zip(&replayed).enumerate() {
+            if expected != actual {
+                println!(
+                    "Discrepancy with seed {} at move {}: {:?} != {:?}",
+                    seed, step, expected, actual
+                );
+                discrepancies += 1;
+            }
+        }
+        if sweeps.len() != replayed.len() {
+            println!(
+                "Discrepancy with seed {} in length: {} != {} moves",
+                seed,
+                sweeps.len(),
+                replayed.len()
+            );
+            discrepancies += 1;
+        }
+    }
 
-        Ok(Self { class, presets })
+    println!(
+        "Compared {} boards from seed {}, {} discrepancies",
+        iterations, base, discrepancies
+    );
+    if discrepancies > 0 {
+        return Err(anyhow!("Backends disagree on {} moves", discrepancies));
     }
+    Ok(())
+}
+
+fn parse_mines_range(s: &str) -> Result<Range<i32>, String> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<i32>()
+            .map_err(|e| format!("Bad mine count `{}`: {}", value, e))
+    };
+    if let Some((from, to)) = s.split_once("..=") {
+        let from = parse(from)?;
+        let end = parse(to)?
+            .checked_add(1)
+            .ok_or_else(|| format!("Bad mine count `{}`: too large", to))?;
+        Ok(from..end)
+    } else if let Some((from, to)) = s.split_once("..") {
+        Ok(parse(from)?..parse(to)?)
+    } else {
+        Err(format!("Expected a range like `30..60`, got `{}`", s))
+    }
+}
+
+/// Apply the dimension options on top of the preset of the mode
+fn custom_preset(cli: &Cli, preset: Preset) -> Result<Preset> {
+    let width = cli.width.unwrap_or(preset.width);
+    let height = cli.height.unwrap_or(preset.height);
+    if let Some(formula) = &cli.mines_formula {
+        return Preset::with_formula(width, height, formula);
+    }
+    match (cli.mines, cli.density) {
+        (_, Some(density)) => Preset::with_density(width, height, density),
+        (Some(mines), _) => Preset::new(width, height, mines),
+        (None, None) => Preset::new(width, height, preset.mines),
+    }
+}
 
-    fn build(&self, mode: Mode) -> Result<PythonMinefield<'a>> {
-        let args = self
-            .presets
-            .get(&mode)
-            .ok_or_else(|| anyhow!("Mode not found"))?;
-        let field = self.class.call((), Some(args.3))?;
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GridTopology {
+    /// 8 neighbors
+    Square,
+    /// Hexagons with 6 neighbors, odd rows shifted to the right
+    Hex,
+}
 
-        Ok(PythonMinefield {
-            field,
-            width: args.0,
-            height: args.1,
-            number_of_mines: args.2,
-        })
+impl GridTopology {
+    fn topology(self) -> &'static dyn Topology {
+        match self {
+            GridTopology::Square => &Square,
+            GridTopology::Hex => &Hex,
+        }
     }
 }
 
-trait Minefield {
-    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell>;
-    fn width(&self) -> i32;
-    fn height(&self) -> i32;
-    fn number_of_mines(&self) -> i32;
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SolverKind {
+    /// Guess by the mine probabilities, with `--guess-strategy`
+    Smart,
+    /// Guess any unknown cell, uniformly at random, repeatable with `--seed`
+    Random,
+    /// Never guess, like `--logic-only`
+    LogicOnly,
 }
 
-#[derive(Debug)]
-struct PythonMinefield<'a> {
-    field: &'a PyAny,
-    width: i32,
-    height: i32,
-    number_of_mines: i32,
+/// The variants of `Mode`, and `tui` in front of one. Not a flattened `Mode`, its external
+/// subcommand would take `tui` as preset name.
+#[derive(Subcommand)]
+enum Command {
+    /// Step through a single game full screen, a key press per solver step
+    Tui {
+        #[clap(subcommand)]
+        mode: Mode,
+    },
+    Beginner,
+    Intermediate,
+    Expert,
+    /// Named preset from the `--presets` file
+    #[clap(external_subcommand)]
+    Custom(Vec<String>),
 }
 
-impl<'a> Minefield for PythonMinefield<'a> {
-    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell> {
-        let result = self.field.call_method("sweep_cell", (column, row), None);
-        match result {
-            Ok(result) => Ok(Cell::Number(result.extract()?)),
-            Err(e) if format!("{}", e) == "ExplosionException: " => Ok(Cell::Mine),
-            Err(e) => Err(e.into()),
+impl Cli {
+    fn mode(&self) -> Mode {
+        match self.command.as_ref().expect("main checks the mode") {
+            Command::Tui { mode } => mode.clone(),
+            Command::Beginner => Mode::Beginner,
+            Command::Intermediate => Mode::Intermediate,
+            Command::Expert => Mode::Expert,
+            Command::Custom(args) => Mode::Custom(args.clone()),
         }
     }
 
-    fn width(&self) -> i32 {
-        self.width
+    fn tui(&self) -> bool {
+        matches!(self.command, Some(Command::Tui { .. }))
     }
 
-    fn height(&self) -> i32 {
-        self.height
+    fn solver_options(&self) -> SolverOptions {
+        SolverOptions {
+            csp_node_limit: self.csp_node_limit,
+            guess_strategy: match self.solver {
+                SolverKind::Random => GuessStrategy::Random,
+                SolverKind::Smart | SolverKind::LogicOnly => self.guess_strategy,
+            },
+            first_guess: self.first,
+            max_board_cells: self.max_board_cells,
+            track_missed_deductions: self.missed_deductions,
+            lookahead: self.lookahead,
+            logic_only: self.logic_only || self.solver == SolverKind::LogicOnly,
+            strict_no_guess: self.assume_no_guess_needed,
+            move_budget: self.budget_moves,
+            deduce_order: self.deduce_order,
+            sweep_order: self.sweep_order,
+            flagging: self.flagging,
+            guess_seed: self.seed.unwrap_or_else(rand::random),
+        }
     }
+}
 
-    fn number_of_mines(&self) -> i32 {
-        self.number_of_mines
+fn solve_file(cli: &Cli, path: &Path) -> Result<Option<Status>> {
+    let source = if path == Path::new("-") {
+        let mut source = String::new();
+        io::stdin()
+            .read_to_string(&mut source)
+            .context("Failed to read the board from stdin")?;
+        source
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read board file {}", path.display()))?
+    };
+
+    let mut boards = RustMinefield::parse_all(&source)?;
+    if boards.len() == 1 {
+        let BoardFile {
+            minefield,
+            revealed,
+            regions,
+        } = boards.remove(0);
+        let mut minefield = minefield.with_topology(cli.topology.topology());
+        let solver = board_solver(cli, &mut minefield, &revealed, regions)?;
+        return play(cli, solver);
+    }
+
+    // Several boards only report how each one went
+    let total = boards.len();
+    let mut won = 0;
+    for (i, board) in boards.into_iter().enumerate() {
+        let BoardFile {
+            minefield,
+            revealed,
+            regions,
+        } = board;
+        let mut minefield = minefield.with_topology(cli.topology.topology());
+        let outcome = board_solver(cli, &mut minefield, &revealed, regions)
+            .and_then(|mut solver| {
+                let outcome = solver.solve()?;
+                warn_flag_mismatch(outcome.status, solver.info());
+                Ok(outcome)
+            })
+            .with_context(|| format!("Board {}", i + 1))?;
+        if outcome.status == Status::Won {
+            won += 1;
+        }
+        if !cli.quiet {
+            println!(
+                "Board {}: {:?}, luck: {}",
+                i + 1,
+                outcome.status,
+                outcome.luck
+            );
+        }
     }
+    if !cli.quiet {
+        println!("Solved {}/{} boards", won, total);
+    }
+
+    Ok(None)
 }
 
-struct RustMinefield {
-    field: Vec<bool>,
-    width: i32,
-    height: i32,
-    number_of_mines: i32,
+/// Solver for a board from a board file, with its revealed cells and region hints
+fn board_solver<'a>(
+    cli: &Cli,
+    minefield: &'a mut RustMinefield,
+    revealed: &[(Pos, Cell)],
+    regions: Vec<Region>,
+) -> Result<Solver<'a, RustMinefield>> {
+    let mut solver = Solver::with_revealed(minefield, cli.solver_options(), revealed)?;
+    for region in regions {
+        solver.add_region(region)?;
+    }
+    solver.check_consistency()?;
+    Ok(solver)
 }
 
-impl RustMinefield {
-    fn new(mode: Mode) -> Self {
-        let (width, height, number_of_mines) = match mode {
-            Mode::Beginner => (10, 10, 10),
-            Mode::Intermediate => (16, 16, 40),
-            Mode::Expert => (30, 16, 99),
-        };
+/// Board for `--first-safe-center`, the seed goes to stderr so stdout stays a board file
+fn first_safe_center(cli: &Cli, preset: Preset) -> Result<()> {
+    if cli.rng == MineRng::Thread {
+        return Err(anyhow!("--first-safe-center needs a seeded RNG"));
+    }
+    let options = SolverOptions {
+        first_guess: FirstGuess::Center,
+        ..cli.solver_options()
+    };
 
-        Self {
-            field: Vec::new(),
-            width,
-            height,
-            number_of_mines,
+    let start = cli.seed.unwrap_or(0);
+    for seed in (start..).take(cli.max_attempts) {
+        let mut minefield = RustMinefield::with_seed(preset, seed)
+            .with_topology(cli.topology.topology())
+            .with_rng(cli.rng)
+            .with_compat(cli.compat)
+            .with_max_cells(cli.max_board_cells);
+        let mut solver = Solver::with_options(&mut minefield, options)?;
+        if solver.solve_without_guessing()? {
+            eprintln!("Seed {} solves without guessing", seed);
+            print!("{}", minefield.to_board_file()?);
+            return Ok(());
         }
     }
+    Err(anyhow!(
+        "No board solves without guessing in {} attempts",
+        cli.max_attempts
+    ))
+}
 
-    fn get(&mut self, col: i32, row: i32) -> Option<bool> {
-        if col < 0 || col >= self.width || row < 0 || row >= self.height {
-            return None;
-        }
+/// Self-test for `--stress`, errors are fine but a panic is a bug
+fn stress(cli: &Cli, preset: Preset) -> Result<()> {
+    if cli.rng == MineRng::Thread {
+        return Err(anyhow!("--stress needs a seeded RNG"));
+    }
 
-        let index: usize = (col + row * self.width).try_into().unwrap();
+    let start = cli.seed.unwrap_or(0);
+    let mut errors = 0;
+    for seed in start.. {
+        let game = std::panic::catch_unwind(|| -> Result<Status> {
+            let mut minefield = RustMinefield::with_seed(preset, seed)
+                .with_topology(cli.topology.topology())
+                .with_rng(cli.rng)
+                .with_compat(cli.compat)
+                .with_max_cells(cli.max_board_cells);
+            let mut solver = Solver::with_options(&mut minefield, cli.solver_options())?;
+            Ok(solver.solve()?.status)
+        });
+        match game {
+            Ok(Ok(_)) => (),
+            Ok(Err(_)) => errors += 1,
+            Err(_) => {
+                return Err(anyhow!(
+                    "Seed {} panicked, after {} games without a panic",
+                    seed,
+                    seed - start
+                ))
+            }
+        }
+        let played = seed - start + 1;
+        if played.is_multiple_of(10_000) {
+            eprintln!("{} games without a panic, {} errors", played, errors);
+        }
+    }
+    Ok(())
+}
 
-        if self.field.is_empty() {
-            let size: usize = (self.width * self.height).try_into().unwrap();
-            self.field = vec![false; size];
+fn resume(cli: &Cli, path: &Path) -> Result<Option<Status>> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read saved game {}", path.display()))?;
+    let snapshot: SolverSnapshot = serde_json::from_str(&source)?;
+    let minefield = RustMinefield::from_saved(snapshot.layout.clone())?;
+    let mut minefield = minefield.with_topology(cli.topology.topology());
+    let solver = Solver::from_snapshot(&mut minefield, cli.solver_options(), snapshot)?;
 
-            let mut rng = thread_rng();
+    play(cli, solver)
+}
 
-            let mut mines_left = self.number_of_mines;
-            while mines_left != 0 {
-                let random_index = rng.gen_range(0..size);
-                if random_index != index && !self.field[random_index] {
-                    self.field[random_index] = true;
-                    mines_left -= 1;
+/// Play and report a single game, returns `None` when `--steps` paused it
+fn play<M: Minefield>(cli: &Cli, mut solver: Solver<'_, M>) -> Result<Option<Status>> {
+    let outcome = match cli.steps {
+        Some(steps) => {
+            for _ in 0..steps {
+                if solver.outcome().is_some() {
+                    break;
                 }
+                solver.step()?;
             }
+            solver.outcome()
         }
+        None if cli.dump_constraints || cli.dump_components => Some(solve_dumping(
+            &mut solver,
+            cli.dump_constraints,
+            cli.dump_components,
+        )?),
+        None => Some(solver.solve()?),
+    };
+    if let Some(outcome) = outcome {
+        warn_flag_mismatch(outcome.status, solver.info());
+    }
 
-        Some(self.field[index])
+    if let Some(path) = &cli.save {
+        let snapshot = serde_json::to_string_pretty(&solver.save()?)?;
+        std::fs::write(path, snapshot)
+            .with_context(|| format!("Failed to save the game to {}", path.display()))?;
     }
 
-    fn neighbors(&mut self, col: i32, row: i32) -> u8 {
-        NEIGHBORS
-            .iter()
-            .map(|(c, r)| -> u8 { self.get(col + c, row + r).unwrap_or(false).into() })
-            .sum()
+    if let Some(path) = &cli.export_layout {
+        let board = solver.reconstruct_layout()?.to_board_file()?;
+        std::fs::write(path, board)
+            .with_context(|| format!("Failed to write the layout to {}", path.display()))?;
     }
-}
 
-impl Minefield for RustMinefield {
-    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell> {
-        match self.get(column, row).unwrap() {
-            true => Ok(Cell::Mine),
-            false => Ok(Cell::Number(self.neighbors(column, row))),
-        }
+    if let Some(path) = &cli.out {
+        write_board(&solver, path)
+            .with_context(|| format!("Failed to write the board to {}", path.display()))?;
     }
 
-    fn width(&self) -> i32 {
-        self.width
+    if !cli.quiet {
+        if cli.explain {
+            print_moves(solver.moves());
+        }
+        solver.render(&mut io::stdout().lock(), cli.render, &cli.glyphs.style())?;
+
+        println!();
+        match outcome {
+            Some(outcome) => {
+                println!(
+                    "{:?}, luck: {}, log luck: {}",
+                    outcome.status, outcome.luck, outcome.log_luck
+                );
+                if cli.fun {
+                    print_banner(outcome.status);
+                }
+            }
+            None => println!("Paused"),
+        }
+        if cli.missed_deductions {
+            print_missed_deductions(solver.missed_deductions());
+        }
+        if cli.relax_stats {
+            print_relax_stats(solver.relax_iterations());
+        }
+        if let Some(budget) = cli.budget_moves {
+            println!(
+                "Opened {:.1}% of the safe cells within {} moves",
+                100.0 * opened_fraction(&solver),
+                budget
+            );
+        }
+        if cli.efficiency {
+            match solver.three_bv() {
+                Some(three_bv) => print_efficiency(three_bv, solver.clicks(), solver.flag_clicks()),
+                None => println!("3BV unknown for this minefield"),
+            }
+        }
+        if cli.timings {
+            print_timings(solver.timings());
+        }
     }
 
-    fn height(&self) -> i32 {
-        self.height
+    Ok(outcome.map(|outcome| outcome.status))
+}
+
+/// Let a human play with commands from stdin, returns `None` when they quit
+fn interactive<M: Minefield>(cli: &Cli, mut solver: Solver<'_, M>) -> Result<Option<Status>> {
+    println!("Commands: `s COL ROW` sweep, `f COL ROW` flag or unflag, `h` hint,");
+    println!("          `a` let the solver make a move, `q` quit");
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        println!();
+        solver.render(&mut io::stdout().lock(), cli.render, &cli.glyphs.style())?;
+        if let Some(outcome) = solver.outcome() {
+            println!("{:?}", outcome.status);
+            if cli.fun {
+                print_banner(outcome.status);
+            }
+            return Ok(Some(outcome.status));
+        }
+
+        print!("> ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(None),
+        };
+
+        let pos = |col: &str, row: &str| -> Result<Pos> { Ok(Pos(col.parse()?, row.parse()?)) };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words[..] {
+            ["s", col, row] => pos(col, row).and_then(|pos| Ok(solver.sweep(pos).map(|_| ())?)),
+            ["f", col, row] => pos(col, row).and_then(|pos| Ok(solver.toggle_flag(pos)?)),
+            ["h"] => solver.suggest().map_err(Into::into).map(|suggestion| {
+                println!("Hint: {}", suggestion);
+            }),
+            ["a"] => solver.step().map_err(Into::into).map(|_| ()),
+            ["q"] => return Ok(None),
+            _ => Err(anyhow!("Unknown command `{}`", line.trim())),
+        };
+        if let Err(e) = result {
+            println!("{}", e);
+        }
     }
+}
 
-    fn number_of_mines(&self) -> i32 {
-        self.number_of_mines
+const WIN_BANNER: &str = r"
+ __        __          _
+ \ \      / /__  _ __ | |
+  \ \ /\ / / _ \| '_ \| |
+   \ V  V / (_) | | | |_|
+    \_/\_/ \___/|_| |_(_)
+";
+
+const BOOM_BANNER: &str = r"
+  ____   ___   ___  __  __
+ | __ ) / _ \ / _ \|  \/  |
+ |  _ \| | | | | | | |\/| |
+ | |_) | |_| | |_| | |  | |
+ |____/ \___/ \___/|_|  |_|
+";
+
+/// Write the board in the format of the extension of `path`
+fn write_board<M: Minefield>(solver: &Solver<'_, M>, path: &Path) -> Result<()> {
+    let mut out = Vec::new();
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::to_writer_pretty(&mut out, &solver.board_state())?,
+        Some("svg") => solver.render_svg(&mut out)?,
+        Some("txt") => solver.render(&mut out, Render::Plain, &RenderStyle::default())?,
+        Some(ext) => {
+            return Err(anyhow!(
+                "Unknown format `.{}`, expected `.json`, `.svg` or `.txt`",
+                ext
+            ))
+        }
+        None => return Err(anyhow!("No extension to pick `.json`, `.svg` or `.txt` by")),
     }
+    std::fs::write(path, out)?;
+    Ok(())
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-struct Pos(i32, i32);
+/// `solve`, but prints the constraints and/or the border components the solver was stuck on
+/// before each guess
+fn solve_dumping<M: Minefield>(
+    solver: &mut Solver<'_, M>,
+    constraints: bool,
+    components: bool,
+) -> Result<Outcome> {
+    loop {
+        if let Some(outcome) = solver.outcome() {
+            return Ok(outcome);
+        }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum Cell {
-    Unknown,
-    Flag,
-    Number(u8),
-    Mine,
+        // A step either deduces or guesses, so these are what the guess had to go on
+        let active = solver.active_constraints();
+        let border = solver.border_components();
+        let seen = solver.moves().len();
+        solver.step()?;
+        let guess = solver.moves()[seen..]
+            .iter()
+            .find(|m| matches!(m.reason, Reason::Guess { .. }));
+        if let Some(guess) = guess {
+            if constraints {
+                println!("Stuck on {} constraints:", active.len());
+                for (number, constraint) in active {
+                    match number {
+                        Some(Pos(col, row)) => println!("  ({},{}): {}", col, row, constraint),
+                        None => println!("  region: {}", constraint),
+                    }
+                }
+            }
+            if components {
+                let sizes: Vec<String> = border.iter().map(|c| c.len().to_string()).collect();
+                println!(
+                    "Stuck on {} components, sizes {}",
+                    border.len(),
+                    sizes.join(" ")
+                );
+            }
+            println!("Then {}", guess);
+        }
+    }
 }
 
-struct Solver<'a, T: Minefield> {
-    minefield: &'a mut T,
-    board: Vec<Cell>,
-    flags: i32,
-    unknowns: i32,
+/// Worked examples for `--examples`, each with what it shows
+const EXAMPLES: &[(&str, &str)] = &[
+    (
+        "Win rate over 1000 expert games, with a 95% confidence interval",
+        "rusty_mines --native -i 1000 expert",
+    ),
+    (
+        "The same boards on every run, played on 4 threads",
+        "rusty_mines --native --seed 0 -i 1000 --threads 4 expert",
+    ),
+    (
+        "One CSV line per game, to analyze elsewhere",
+        "rusty_mines --native --seed 0 -i 1000 --csv intermediate > games.csv",
+    ),
+    (
+        "Compare guess strategies on the same boards",
+        "rusty_mines --native --seed 0 -i 1000 --guess-strategy progress expert",
+    ),
+    (
+        "How much the mine probabilities help, against guessing at random",
+        "rusty_mines --native --seed 0 -i 1000 --solver random expert",
+    ),
+    (
+        "A single seeded game, every move with the reason for it",
+        "rusty_mines --native --seed 42 --explain beginner",
+    ),
+    (
+        "Solve a board file, `-` reads it from stdin",
+        "rusty_mines --file board.txt beginner",
+    ),
+    (
+        "Heatmap of the mine probabilities, 20 steps into a game",
+        "rusty_mines --native --seed 42 --steps 20 --render heat expert",
+    ),
+    (
+        "Watch a game full screen, a step per key press",
+        "rusty_mines --native --seed 42 tui expert",
+    ),
+    (
+        "Play yourself, with hints from the solver",
+        "rusty_mines --native --interactive beginner",
+    ),
+    (
+        "Solve boards until the solver panics, then print the seed",
+        "rusty_mines --stress --seed 0 expert",
+    ),
+];
+
+fn print_examples() {
+    for (i, (what, command)) in EXAMPLES.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}:", what);
+        println!("    {}", command.bold());
+    }
 }
 
-impl<'a, T: Minefield> Solver<'a, T> {
-    fn new(minefield: &'a mut T) -> Result<Self> {
-        let size: usize = (minefield.width() * minefield.height()).try_into()?;
-        Ok(Self {
-            minefield,
-            board: vec![Cell::Unknown; size],
-            flags: 0,
-            unknowns: size.try_into().unwrap(),
-        })
+/// Warn about a board cleared with wrong flags, which the solver leaves `Incomplete`
+fn warn_flag_mismatch(status: Status, info: BoardInfo) {
+    if status == Status::Incomplete && info.flag_mismatch() {
+        eprintln!(
+            "Warning: board cleared with {} flags but {} mines",
+            info.flags_placed, info.number_of_mines
+        );
     }
+}
 
-    fn index(&self, pos: Pos) -> Option<usize> {
-        let Pos(col, row) = pos;
-        if col < 0 || col >= self.minefield.width() || row < 0 || row >= self.minefield.height() {
-            return None;
+fn print_banner(status: Status) {
+    match status {
+        Status::Won => println!("{}", WIN_BANNER.bold().green()),
+        Status::Exploded { at: Pos(col, row) } => {
+            println!("{}", BOOM_BANNER.bold().red());
+            println!("Stepped on the mine at column {}, row {}", col, row);
         }
+        Status::Incomplete => (),
+    }
+}
 
-        let index: usize = (col + row * self.minefield.width()).try_into().unwrap();
-        Some(index)
+fn print_moves(moves: &[Move]) {
+    for m in moves {
+        println!("{}", m);
     }
+    println!();
+}
 
-    fn get(&self, pos: Pos) -> Option<Cell> {
-        self.index(pos).map(|i| self.board[i])
+fn print_missed_deductions(missed: &[Pos]) {
+    println!("Missed deductions: {}", missed.len());
+    for Pos(col, row) in missed {
+        println!("  ({},{})", col, row);
     }
+}
 
-    fn uncover(&mut self, pos: Pos) -> Result<Cell> {
-        let Pos(col, row) = pos;
-        let cell = self.minefield.sweep_cell(col, row)?;
-        let i = self.index(pos).ok_or_else(|| anyhow!("Bad index"))?;
-        assert!(self.board[i] == Cell::Unknown);
-        self.board[i] = cell;
-        self.unknowns -= 1;
-        Ok(cell)
+/// Distribution of the relaxation iterations per guess, guesses that used all of them didn't
+/// converge
+fn print_relax_stats(iterations: &[usize]) {
+    let values: Vec<f64> = iterations.iter().map(|n| *n as f64).collect();
+    let capped = iterations
+        .iter()
+        .filter(|n| **n >= RELAX_ITERATIONS)
+        .count();
+    match (percentile(&values, 0.5), percentile(&values, 0.9)) {
+        (Some(p50), Some(p90)) => println!(
+            "Relaxation iterations over {} guesses: mean {:.1}, median {}, p90 {}, {} hit the cap of {}",
+            values.len(),
+            values.iter().sum::<f64>() / values.len() as f64,
+            p50,
+            p90,
+            capped,
+            RELAX_ITERATIONS
+        ),
+        _ => println!("Relaxation not used"),
     }
+}
+
+/// Share of the safe cells that the solver opened
+fn opened_fraction<M: Minefield>(solver: &Solver<'_, M>) -> f64 {
+    let info = solver.info();
+    let safe = f64::from(info.width) * f64::from(info.height) - f64::from(info.number_of_mines);
+    let opened = solver
+        .moves()
+        .iter()
+        .filter(|m| matches!(m.cell, Cell::Number(_) | Cell::Hidden))
+        .count();
+    opened as f64 / safe
+}
 
-    fn plant_flag(&mut self, pos: Pos) -> Result<()> {
-        let i = self.index(pos).ok_or_else(|| anyhow!("Bad index"))?;
-        assert!(self.board[i] == Cell::Unknown);
-        self.board[i] = Cell::Flag;
-        self.flags += 1;
-        self.unknowns -= 1;
-        Ok(())
+/// `total / count` with two decimals, `-` when there's nothing to average
+fn per_game(total: usize, count: usize) -> String {
+    if count == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.2}", total as f64 / count as f64)
     }
+}
 
-    fn neighbors(&self, pos: Pos) -> Vec<(Pos, Cell)> {
-        let Pos(col, row) = pos;
-        let r: Vec<(Pos, Cell)> = NEIGHBORS
-            .iter()
-            .filter_map(|(c, r)| {
-                self.get(Pos(col + c, row + r))
-                    .map(|cell| (Pos(col + c, row + r), cell))
-            })
-            .collect();
-
-        r
-    }
-
-    fn solve(&mut self) -> Result<(bool, f32)> {
-        let mut active: Vec<Pos> = Vec::new();
-        let mut luck = 1f32;
-
-        // First guess: 0,0 why not
-        let mut next = vec![Pos(0, 0)];
-
-        loop {
-            active.clear();
-            std::mem::swap(&mut active, &mut next);
-            let mut new_info = false;
-
-            for pos in active.iter().copied() {
-                let cell = self
-                    .get(pos)
-                    .ok_or_else(|| anyhow!("Bad active cell location"))?;
-
-                match cell {
-                    Cell::Number(mines) => {
-                        let mines: i32 = mines.into();
-                        let neighbors = self.neighbors(pos);
-                        let flags: i32 = neighbors
-                            .iter()
-                            .filter(|(_, cell)| matches!(cell, Cell::Flag))
-                            .count()
-                            .try_into()
-                            .unwrap();
-                        let unknowns: i32 = neighbors
-                            .iter()
-                            .filter(|(_, cell)| matches!(cell, Cell::Unknown))
-                            .count()
-                            .try_into()
-                            .unwrap();
-
-                        if unknowns == 0 {
-                            // Done
-                        } else if mines == flags {
-                            for p in neighbors.iter().filter_map(|(pos, cell)| {
-                                matches!(cell, Cell::Unknown).then(|| *pos)
-                            }) {
-                                self.uncover(p)?;
-                                next.push(p);
-                            }
-                            new_info = true;
-                        } else if unknowns + flags == mines {
-                            for p in neighbors.iter().filter_map(|(pos, cell)| {
-                                matches!(cell, Cell::Unknown).then(|| *pos)
-                            }) {
-                                self.plant_flag(p)?;
-                            }
-                            new_info = true;
-                        } else {
-                            next.push(pos);
-                        }
-                    }
-                    Cell::Unknown => {
-                        self.uncover(pos)?;
-                        next.push(pos);
-                        new_info = true;
-                    }
-                    Cell::Mine => return Ok((false, luck)),
-                    _ => (),
-                }
-            }
+fn print_efficiency(three_bv: usize, clicks: usize, flags: usize) {
+    let efficiency = |clicks: usize| {
+        if clicks == 0 {
+            "-".to_string()
+        } else {
+            format!("{:.3}", three_bv as f64 / clicks as f64)
+        }
+    };
+    println!(
+        "3BV {}, clicks {}, flags {}, efficiency {}, {} counting the flags",
+        three_bv,
+        clicks,
+        flags,
+        efficiency(clicks),
+        efficiency(clicks + flags)
+    );
+}
 
-            // Already done
-            if self.unknowns == 0 {
-                break;
-            }
+fn print_histogram(title: &str, histogram: &Histogram) {
+    const WIDTH: u64 = 40;
+    println!("{}:", title);
+    let max = histogram.counts().iter().copied().max().unwrap_or(0).max(1);
+    for (i, count) in histogram.counts().iter().enumerate() {
+        let (low, high) = histogram.bin_range(i);
+        let bar = "#".repeat(usize::try_from(count * WIDTH / max).unwrap_or(0));
+        println!(
+            "  {:.1}–{:.1} |{:<width$}| {}",
+            low,
+            high,
+            bar,
+            count,
+            width = WIDTH as usize
+        );
+    }
+}
 
-            let remaining_mines = self.minefield.number_of_mines() - self.flags;
+/// `counts[n]` is how many opened cells showed `n`
+fn print_number_stats(counts: &[u64]) {
+    const WIDTH: u64 = 40;
+    let total: u64 = counts.iter().sum();
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    println!("Revealed numbers:");
+    for (number, count) in counts.iter().enumerate() {
+        let bar = "#".repeat(usize::try_from(count * WIDTH / max).unwrap_or(0));
+        println!(
+            "  {} |{:<width$}| {} ({:.1}%)",
+            number,
+            bar,
+            count,
+            100.0 * *count as f64 / total.max(1) as f64,
+            width = WIDTH as usize
+        );
+    }
+}
 
-            // Uncover remaining cells when all mines are flagged, then we are done
-            if remaining_mines == 0 {
-                for col in 0..self.minefield.width() {
-                    for row in 0..self.minefield.height() {
-                        let pos = Pos(col, row);
-                        if let Some(Cell::Unknown) = self.get(pos) {
-                            self.uncover(pos)?;
-                        }
-                    }
-                }
-                break;
+fn print_timings(timings: Timings) {
+    println!(
+        "Deduction {:?}, guessing {:?}",
+        timings.deduction, timings.guessing
+    );
+}
+
+/// Win rate per mine count on the board of `preset`, as a table
+fn mines_curve<T, M>(
+    cli: &Cli,
+    preset: Preset,
+    range: Range<i32>,
+    iterations: usize,
+    mut new: T,
+) -> Result<()>
+where
+    T: FnMut(Preset) -> Result<M>,
+    M: Minefield,
+{
+    println!("| Mines | Density | Win rate | 95% CI        |");
+    println!("|-------|---------|----------|---------------|");
+    for mines in range {
+        let preset = Preset::new(preset.width, preset.height, mines)?;
+        let mut won = 0;
+        for _ in 0..iterations {
+            let mut minefield = new(preset)?;
+            let mut solver = Solver::with_options(&mut minefield, cli.solver_options())?;
+            if solver.solve()?.status == Status::Won {
+                won += 1;
             }
+        }
+
+        let (low, high) = wilson_interval(won, u64::try_from(iterations)?, 1.96)
+            .ok_or_else(|| anyhow!("No games played"))?;
+        println!(
+            "| {:>5} | {:>6.1}% | {:>7.1}% | {:>5.1}%–{:>5.1}% |",
+            mines,
+            100.0 * f64::from(mines) / f64::from(preset.width * preset.height),
+            100.0 * won as f64 / iterations as f64,
+            100.0 * low,
+            100.0 * high
+        );
+    }
+    Ok(())
+}
 
-            if new_info {
-                continue;
+/// How often the guesses of the solver are optimal, and the win probability the others give
+/// up, next to the win rate of the solver and of optimal play
+fn optimality_gap<T, M>(
+    cli: &Cli,
+    preset: Preset,
+    iterations: usize,
+    cell_limit: usize,
+    mut new: T,
+) -> Result<()>
+where
+    T: FnMut(Preset) -> Result<M>,
+    M: Minefield,
+{
+    let (mut guesses, mut optimal, mut won) = (0, 0, 0);
+    let (mut gap, mut optimal_wins) = (Welford::default(), Welford::default());
+    for _ in 0..iterations {
+        let mut minefield = new(preset)?;
+        let mut solver = Solver::with_options(&mut minefield, cli.solver_options())?;
+        optimal_wins.push(solver.win_probability(cell_limit)?);
+        while solver.outcome().is_none() {
+            if let Suggestion::Guess { pos, .. } = solver.suggest()? {
+                let values = solver.guess_values(cell_limit)?;
+                let best = values.iter().map(|(_, v)| *v).fold(0f64, f64::max);
+                let (_, chosen) = values
+                    .iter()
+                    .find(|(p, _)| *p == pos)
+                    .ok_or_else(|| anyhow!("Guess {:?} is not an unknown cell", pos))?;
+                guesses += 1;
+                if best - chosen < 1e-9 {
+                    optimal += 1;
+                }
+                gap.push(best - chosen);
             }
+            solver.step()?;
+        }
+        if solver.outcome().map(|outcome| outcome.status) == Some(Status::Won) {
+            won += 1;
+        }
+    }
 
-            // Simple algo didn't find new info, try heavier iterative algo now.
+    if guesses == 0 {
+        println!("Optimal guesses 0/0, no game needed a guess");
+    } else {
+        println!(
+            "Optimal guesses {}/{} ({:.1}%), mean win probability lost per guess {:.4}",
+            optimal,
+            guesses,
+            100.0 * optimal as f64 / guesses as f64,
+            gap.mean()
+        );
+    }
+    println!(
+        "Won {}/{} ({:.1}%), optimal play wins {:.1}%",
+        won,
+        iterations,
+        100.0 * won as f64 / iterations as f64,
+        100.0 * optimal_wins.mean()
+    );
+    Ok(())
+}
 
-            let naive_chance = remaining_mines as f32 / self.unknowns as f32;
+/// What a batch keeps of a finished game
+struct GameStats {
+    outcome: Outcome,
+    /// Solved without guessing, only with `--measure-fairness`
+    no_guess: bool,
+    missed: usize,
+    /// Only with `--relax-stats`
+    relax_iterations: Vec<usize>,
+    timings: Timings,
+    opened: f64,
+    /// Opened cells per number
+    numbers: Vec<u64>,
+    guesses: usize,
+    /// 3BV, clicks and flags, when the minefield knows its 3BV
+    efficiency: Option<(usize, usize, usize)>,
+}
 
-            let mut probs: HashMap<Pos, f32> = HashMap::new();
-            for pos in active.iter().copied() {
-                let neighbors = self.neighbors(pos);
-                probs.extend(neighbors.iter().filter_map(|(pos, cell)| {
-                    matches!(cell, Cell::Unknown).then(|| (*pos, naive_chance))
-                }));
+/// Play a game of a batch to the end
+fn play_batch_game<M: Minefield>(cli: &Cli, minefield: &mut M) -> Result<GameStats> {
+    let mut solver = Solver::with_options(minefield, cli.solver_options())?;
+    // The solver continues after the deductions that don't need a guess, so this leaves the
+    // outcome the same
+    let no_guess = cli.measure_fairness && solver.solve_without_guessing()?;
+    let outcome = solver.solve()?;
+    warn_flag_mismatch(outcome.status, solver.info());
+
+    let mut numbers: Vec<u64> = Vec::new();
+    for m in solver.moves() {
+        if let Cell::Number(number) = m.cell {
+            let number = usize::from(number);
+            if numbers.len() <= number {
+                numbers.resize(number + 1, 0);
             }
+            numbers[number] += 1;
+        }
+    }
+    Ok(GameStats {
+        outcome,
+        no_guess,
+        missed: solver.missed_deductions().len(),
+        relax_iterations: if cli.relax_stats {
+            solver.relax_iterations().to_vec()
+        } else {
+            Vec::new()
+        },
+        timings: solver.timings(),
+        opened: opened_fraction(&solver),
+        numbers,
+        guesses: solver.guesses(),
+        efficiency: solver
+            .three_bv()
+            .map(|bv| (bv, solver.clicks(), solver.flag_clicks())),
+    })
+}
 
-            for _ in 0..100 {
-                let mut max_correction_diff = 0f32;
-
-                for pos in active.iter().copied() {
-                    let cell = self
-                        .get(pos)
-                        .ok_or_else(|| anyhow!("Bad active cell location"))?;
+/// Returns the status of the game in single game mode, for the exit code
+fn body<T, M>(cli: Cli, preset: Preset, mut new: T) -> Result<Option<Status>>
+where
+    T: FnMut(Preset) -> Result<M>,
+    M: Minefield,
+{
+    let options = cli.solver_options();
 
-                    if let Cell::Number(mines) = cell {
-                        let mines: i32 = mines.into();
-                        let neighbors = self.neighbors(pos);
-                        let flags: i32 = neighbors
-                            .iter()
-                            .filter(|(_, cell)| matches!(cell, Cell::Flag))
-                            .count()
-                            .try_into()
-                            .unwrap();
-                        let unknowns: Vec<Pos> = neighbors
-                            .iter()
-                            .filter_map(|(pos, cell)| matches!(cell, Cell::Unknown).then(|| (*pos)))
-                            .collect();
+    if let (Some(range), Some(iterations)) = (cli.mines_range.clone(), cli.iterations) {
+        mines_curve(&cli, preset, range, iterations, new)?;
+        return Ok(None);
+    }
 
-                        let expected = (mines - flags) as f32;
-                        let sum: f32 = unknowns.iter().map(|pos| *probs.get(pos).unwrap()).sum();
-                        let correction = (expected - sum) / unknowns.len() as f32;
+    if let (Some(cell_limit), Some(iterations)) = (cli.optimality_gap, cli.iterations) {
+        optimality_gap(&cli, preset, iterations, cell_limit, new)?;
+        return Ok(None);
+    }
 
-                        max_correction_diff = f32::max(max_correction_diff, f32::abs(correction));
+    if let Some(iterations) = cli.iterations {
+        // Only the luck of won games, everything else stays constant size
+        let mut luck = if cli.csv {
+            Aggregate::exact()
+        } else {
+            Aggregate::streaming()
+        };
+        let mut log_luck = Welford::default();
+        let mut luck_histogram = Histogram::new(10);
+        let mut numbers: Vec<u64> = Vec::new();
+        let mut opened = Welford::default();
+        let mut no_guess = 0;
+        let (mut missed, mut games_with_missed) = (0, 0);
+        let mut relax_iterations = Vec::new();
+        // Only won games with a known 3BV count
+        let (mut three_bv, mut clicks, mut flags, mut efficiency_games) = (0, 0, 0, 0);
+        // Guesses of won and of lost games
+        let (mut won_guesses, mut lost_guesses) = (0, 0);
+        let mut timings = Timings::default();
+        let (mut exploded, mut incomplete) = (0, 0);
+
+        if cli.csv {
+            println!("game,status,luck,log_luck");
+        }
 
-                        for pos in unknowns {
-                            if let Some(p) = probs.get_mut(&pos) {
-                                *p = f32::clamp(*p + correction, 0f32, 1f32);
-                            }
-                        }
+        // Ctrl-C stops after the current game, so the summary of the games so far still shows
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let handler_flag = interrupted.clone();
+        ctrlc::set_handler(move || handler_flag.store(true, Ordering::SeqCst))?;
+
+        // The games in order, until the first one skipped because of Ctrl-C
+        let mut games: Box<dyn Iterator<Item = Result<GameStats>>> = match cli.threads {
+            Some(threads) => {
+                if !cli.native {
+                    return Err(anyhow!(
+                        "Only native minefields can play on several threads"
+                    ));
+                }
+                let seed = cli.seed.unwrap_or(0);
+                let results = parallel_games(iterations, threads, |game| {
+                    if interrupted.load(Ordering::SeqCst) {
+                        return None;
                     }
+                    let mut minefield =
+                        RustMinefield::with_seed(preset, seed.wrapping_add(game as u64))
+                            .with_topology(cli.topology.topology())
+                            .with_rng(cli.rng)
+                            .with_compat(cli.compat)
+                            .with_max_cells(cli.max_board_cells);
+                    Some(play_batch_game(&cli, &mut minefield))
+                });
+                Box::new(results.into_iter().map_while(|result| result))
+            }
+            None => Box::new((0..iterations).map_while(|_| {
+                if interrupted.load(Ordering::SeqCst) {
+                    return None;
                 }
+                Some(new(preset).and_then(|mut minefield| play_batch_game(&cli, &mut minefield)))
+            })),
+        };
 
-                // Reduce total probability if it is more then the remaining mines
-                let sum: f32 = probs.iter().map(|(_, p)| p).copied().sum();
-                if sum > remaining_mines as f32 {
-                    let correction = (remaining_mines as f32 - sum) / probs.len() as f32;
-                    for (_, p) in probs.iter_mut() {
-                        *p = f32::clamp(*p + correction, 0f32, 1f32);
+        // A game only counts once it is over, so an error leaves the totals untouched
+        let (mut played, mut errored) = (0, 0);
+        for game in 0..iterations {
+            let stats = match games.next() {
+                Some(Ok(stats)) => stats,
+                Some(Err(err)) if cli.keep_going => {
+                    eprintln!("Game {} failed: {:#}", game, err);
+                    if cli.csv {
+                        println!("{},errored,,", game);
                     }
-                    max_correction_diff = f32::max(max_correction_diff, f32::abs(correction));
+                    errored += 1;
+                    continue;
                 }
+                Some(Err(err)) => return Err(err),
+                None => break,
+            };
+            played += 1;
 
-                // Enough conversion, done iterating
-                if max_correction_diff < 0.0001 {
-                    break;
-                }
+            let outcome = stats.outcome;
+            if stats.no_guess {
+                no_guess += 1;
             }
-
-            let sum: f32 = probs.iter().map(|(_, p)| p).copied().sum();
-            let border_unknowns: i32 = probs.len().try_into().unwrap();
-            let isolated_unknowns: i32 = self.unknowns - border_unknowns;
-            let p_other = (remaining_mines as f32 - sum) / (isolated_unknowns as f32);
-
-            let best_guess = probs
-                .iter()
-                .min_by(|(_, p1), (_, p2)| (*p1).partial_cmp(*p2).unwrap());
-
-            // Lazy
-            let pos_other = || {
-                for col in 0..self.minefield.width() {
-                    for row in 0..self.minefield.height() {
-                        let pos = Pos(col, row);
-                        if let Some(Cell::Unknown) = self.get(pos) {
-                            if probs.get(&pos).is_none() {
-                                return pos;
-                            }
-                        }
-                    }
+            relax_iterations.extend_from_slice(&stats.relax_iterations);
+            missed += stats.missed;
+            if stats.missed > 0 {
+                games_with_missed += 1;
+            }
+            timings.deduction += stats.timings.deduction;
+            timings.guessing += stats.timings.guessing;
+            opened.push(stats.opened);
+            if numbers.len() < stats.numbers.len() {
+                numbers.resize(stats.numbers.len(), 0);
+            }
+            for (total, count) in numbers.iter_mut().zip(&stats.numbers) {
+                *total += count;
+            }
+            if cli.csv {
+                println!(
+                    "{},{},{},{}",
+                    game,
+                    outcome.status.name(),
+                    outcome.luck,
+                    outcome.log_luck
+                );
+            }
+            if outcome.status == Status::Won {
+                won_guesses += stats.guesses;
+                if let Some((bv, game_clicks, game_flags)) = stats.efficiency {
+                    three_bv += bv;
+                    clicks += game_clicks;
+                    flags += game_flags;
+                    efficiency_games += 1;
                 }
-                panic!();
-            };
+                luck.push(f64::from(outcome.luck));
+                luck_histogram.push(f64::from(outcome.luck));
+                log_luck.push(outcome.log_luck);
+            } else {
+                lost_guesses += stats.guesses;
+            }
+            match outcome.status {
+                Status::Won => (),
+                Status::Exploded { .. } => exploded += 1,
+                Status::Incomplete => incomplete += 1,
+            }
+        }
 
-            let best_guess = match best_guess {
-                Some((_, p)) if isolated_unknowns > 0 && p_other < *p => (pos_other(), p_other),
-                Some((pos, p)) => (*pos, *p),
-                None => (pos_other(), p_other),
+        if !cli.quiet {
+            if played + errored < iterations {
+                println!("Interrupted after {} games", played + errored);
+            }
+            let summary = luck.summary();
+            let win_rate = match wilson_interval(summary.count, u64::try_from(played)?, 1.96) {
+                Some((low, high)) => format!(
+                    "win rate {:.3} (95% CI {:.3}–{:.3})",
+                    summary.count as f64 / played as f64,
+                    low,
+                    high
+                ),
+                None => "no games".to_string(),
             };
-
-            luck *= 1f32 - best_guess.1;
-
-            let pos = best_guess.0;
-            let cell = self.uncover(pos)?;
-            if let Cell::Mine = cell {
-                return Ok((false, luck));
+            println!(
+                "Solved {}/{}, {}, {}, avg luck {}, geometric mean luck {}",
+                summary.count,
+                played,
+                win_rate,
+                cli.mode(),
+                summary.mean,
+                log_luck.mean().exp()
+            );
+            let lost = played - summary.count as usize;
+            if cli.keep_going {
+                println!(
+                    "Won {}, exploded {}, incomplete {}, errored {}",
+                    summary.count, exploded, incomplete, errored
+                );
+            }
+            println!(
+                "Guesses per game: won {}, lost {}",
+                per_game(won_guesses, summary.count as usize),
+                per_game(lost_guesses, lost)
+            );
+            if cli.measure_fairness {
+                println!(
+                    "Solvable without guessing {}/{} ({})",
+                    no_guess,
+                    played,
+                    no_guess as f64 / played as f64
+                );
+            }
+            if cli.missed_deductions {
+                println!(
+                    "Missed deductions {} in {}/{} games",
+                    missed, games_with_missed, played
+                );
+            }
+            if cli.relax_stats {
+                print_relax_stats(&relax_iterations);
+            }
+            if let Some(budget) = cli.budget_moves {
+                println!(
+                    "Opened {:.1}% of the safe cells within {} moves on average",
+                    100.0 * opened.mean(),
+                    budget
+                );
+            }
+            if cli.efficiency {
+                if efficiency_games == 0 {
+                    println!("3BV unknown for this minefield");
+                } else {
+                    print_efficiency(three_bv, clicks, flags);
+                }
+            }
+            if let (Some(p10), Some(p50), Some(p90)) = (summary.p10, summary.p50, summary.p90) {
+                println!(
+                    "Luck std dev {}, p10 {}, median {}, p90 {}",
+                    summary.std_dev, p10, p50, p90
+                );
+            }
+            if cli.luck_histogram {
+                print_histogram("Luck of won games", &luck_histogram);
+            }
+            if cli.number_stats {
+                print_number_stats(&numbers);
+            }
+            if cli.timings {
+                print_timings(timings);
             }
-            next.push(pos);
         }
 
-        Ok((self.solved(), luck))
-    }
-
-    fn solved(&self) -> bool {
-        let flags: i32 = self
-            .board
-            .iter()
-            .filter(|cell| matches!(cell, Cell::Flag))
-            .count()
-            .try_into()
-            .unwrap();
-        let unknowns: i32 = self
-            .board
-            .iter()
-            .filter(|cell| matches!(cell, Cell::Unknown))
-            .count()
-            .try_into()
-            .unwrap();
-        let mines: i32 = self
-            .board
-            .iter()
-            .filter(|cell| matches!(cell, Cell::Mine))
-            .count()
-            .try_into()
-            .unwrap();
-        unknowns == 0 && mines == 0 && flags == self.minefield.number_of_mines()
-    }
-
-    fn show(&self) {
-        for row in 0..self.minefield.height() {
-            for col in 0..self.minefield.width() {
-                match self.get(Pos(col, row)).unwrap() {
-                    Cell::Flag => print!("{} ", "F".bold().cyan()),
-                    Cell::Unknown => print!(". "),
-                    Cell::Number(0) => print!("  "),
-                    Cell::Number(x) => print!("{} ", x),
-                    Cell::Mine => print!("{} ", "X".bold().red()),
+        Ok(None)
+    } else if cli.repeat_until_win {
+        for attempt in 1..=cli.max_attempts {
+            let mut minefield = new(preset)?;
+            let mut solver = Solver::with_options(&mut minefield, options)?;
+            if solver.solve()?.status == Status::Won {
+                if !cli.quiet {
+                    println!("Won after {} attempts", attempt);
                 }
+                // Already solved, so this only reports it
+                return play(&cli, solver);
             }
-            println!();
+        }
+        Err(anyhow!("No win in {} attempts", cli.max_attempts))
+    } else {
+        let mut minefield = new(preset)?;
+        let solver = Solver::with_options(&mut minefield, options)?;
+
+        if cli.interactive {
+            interactive(&cli, solver)
+        } else if cli.tui() {
+            tui::run(cli.topology.topology(), &cli.glyphs.style(), solver)
+        } else {
+            play(&cli, solver)
         }
     }
 }
 
-#[derive(Parser)]
-#[clap(about, long_about = None)]
-struct Cli {
-    #[clap(subcommand)]
-    mode: Mode,
-
-    #[clap(short, long, value_parser)]
-    iterations: Option<usize>,
+/// `body` with the minefields of the Python challenge
+#[cfg(feature = "python")]
+fn python_body(cli: Cli, preset: Preset) -> Result<Option<Status>> {
+    Python::with_gil(|py| {
+        let builder = MinefieldBuilder::new(py)?;
+        // Built-in modes come from the module, with the size options applied on top
+        let preset = match builder.preset(&cli.mode()) {
+            Some(builtin) => custom_preset(&cli, builtin)?,
+            None => preset,
+        };
+        body(cli, preset, |preset: Preset| builder.build(preset))
+    })
+}
 
-    #[clap(short, long, value_parser)]
-    native: bool,
+#[cfg(not(feature = "python"))]
+fn python_body(_cli: Cli, _preset: Preset) -> Result<Option<Status>> {
+    Err(anyhow!("Built without the Python minefield, use --native"))
 }
 
-fn body<T, M>(cli: Cli, new: T) -> Result<()>
-where
-    T: Fn(Mode) -> Result<M>,
-    M: Minefield,
-{
-    if let Some(iterations) = cli.iterations {
-        let mut success = 0;
-        let mut luck_sum = 0f32;
-        for _ in 0..iterations {
-            let mut minefield = new(cli.mode)?;
-            let mut solver = Solver::new(&mut minefield)?;
-            if let (true, luck) = solver.solve()? {
-                success += 1;
-                luck_sum += luck;
-            }
-        }
+fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    if cli.examples {
+        print_examples();
+        return Ok(());
+    }
+    if cli.command.is_none() {
+        Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "Missing the mode: beginner, intermediate, expert or a preset name",
+            )
+            .exit();
+    }
+    if cli.tui() && (cli.iterations.is_some() || cli.interactive) {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "tui steps through a single game, without --iterations or --interactive",
+            )
+            .exit();
+    }
+    // Without Python the Rust minefield is the only one
+    cli.native |= cfg!(not(feature = "python"));
+    if let Some(name) = &cli.seed_string {
+        cli.seed = Some(seed_from_str(name));
+    }
 
-        println!(
-            "Solved {}/{} successful ({}), {:?}, avg luck {}",
-            success,
-            iterations,
-            success as f32 / iterations as f32,
-            cli.mode,
-            luck_sum / success as f32
-        );
-    } else {
-        let mut minefield = new(cli.mode)?;
-        let mut solver = Solver::new(&mut minefield)?;
+    let presets = match &cli.presets {
+        Some(path) => load_presets(&std::fs::read_to_string(path)?)?,
+        None => HashMap::new(),
+    };
+    let preset = custom_preset(&cli, cli.mode().preset(&presets)?)?;
 
-        let (solved, luck) = solver.solve()?;
-        solver.show();
+    if cli.compare_backends {
+        return compare_backends(&cli, preset, cli.iterations.unwrap_or(1));
+    }
 
-        println!();
-        println!("Solved: {}, luck: {}", solved, luck);
+    if cli.rng == MineRng::Thread && cli.seed.is_some() {
+        return Err(anyhow!("The thread RNG can't be seeded"));
     }
 
-    Ok(())
-}
+    if cli.topology != GridTopology::Square && !cli.native && cli.file.is_none() {
+        return Err(anyhow!("Only native minefields support other topologies"));
+    }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+    if cli.first_safe_center {
+        return first_safe_center(&cli, preset);
+    }
 
-    if cli.native {
-        body(cli, |mode: Mode| -> Result<_> {
-            Ok(RustMinefield::new(mode))
-        })
-    } else {
-        Python::with_gil(|py| {
-            let builder = MinefieldBuilder::new(py)?;
-            body(cli, |mode: Mode| builder.build(mode))
-        })
-    }
-}
-
-#[test]
-fn bla() -> Result<()> {
-    let mut minefield = RustMinefield {
-        field: vec![
-            false, false, false, false, false, false, true, false, false, false, false, false,
-            true, false, false, true,
-        ],
-        width: 4,
-        height: 4,
-        number_of_mines: 3,
-    };
+    if cli.stress {
+        return stress(&cli, preset);
+    }
 
-    let mut solver = Solver::new(&mut minefield)?;
+    let dump = cli.dump_board || cli.emit_python;
+    if cli.compat != Compat::Rejection && !cli.native && !dump {
+        return Err(anyhow!("Only native minefields support --compat"));
+    }
+
+    let status = if let Some(path) = &cli.load {
+        resume(&cli, path)?
+    } else if let Some(path) = &cli.file {
+        solve_file(&cli, path)?
+    } else if cli.native || dump {
+        let mut seed = cli.seed;
+        let topology = cli.topology.topology();
+        let rng = cli.rng;
+        let compat = cli.compat;
+        let max_cells = cli.max_board_cells;
+        let (no_fifty_fifty, max_attempts) = (cli.no_fifty_fifty, cli.max_attempts);
+        let options = cli.solver_options();
+        let mut new = move |preset: Preset| -> Result<_> {
+            for _ in 0..max_attempts.max(1) {
+                let minefield = match &mut seed {
+                    Some(seed) => {
+                        let game_seed = *seed;
+                        *seed = seed.wrapping_add(1);
+                        RustMinefield::with_seed(preset, game_seed)
+                    }
+                    None => RustMinefield::new(preset),
+                };
+                let mut minefield = minefield
+                    .with_topology(topology)
+                    .with_rng(rng)
+                    .with_compat(compat)
+                    .with_max_cells(max_cells);
+                if !no_fifty_fifty || !minefield.has_fifty_fifty(options)? {
+                    return Ok(minefield);
+                }
+            }
+            Err(anyhow!(
+                "Every board had a 50/50 in {} attempts",
+                max_attempts
+            ))
+        };
 
-    solver.solve()?;
-    assert!(solver.solved());
+        if dump {
+            let mut minefield = new(preset)?;
+            minefield.generate(cli.first.pos(preset.width, preset.height, preset.mines))?;
+            if cli.emit_python {
+                print!("{}", minefield.to_python()?);
+            } else {
+                print!("{}", minefield.to_board_file()?);
+            }
+            return Ok(());
+        }
+        body(cli, preset, new)?
+    } else {
+        python_body(cli, preset)?
+    };
 
-    Ok(())
+    // Exit code tells scripts how a single game went
+    match status {
+        Some(Status::Exploded { .. }) => std::process::exit(1),
+        Some(Status::Incomplete) => std::process::exit(2),
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,176 @@
+//! Minefields of the Python challenge, through pyo3. Only with the `python` feature.
+
+use crate::{Cell, Minefield, Mode, Preset, Solver};
+use anyhow::{anyhow, Result};
+use pyo3::{
+    exceptions::{PyIndexError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use std::collections::HashMap;
+
+const SOURCE: &str = include_str!("../lib/decode_demcon3/mineField.py");
+
+pub struct MinefieldBuilder<'a> {
+    py: Python<'a>,
+    class: &'a PyAny,
+    presets: HashMap<Mode, (Preset, &'a PyDict)>,
+}
+
+impl<'a> MinefieldBuilder<'a> {
+    pub fn new(py: Python<'a>) -> Result<Self> {
+        let module = PyModule::from_code(py, SOURCE, "mineField", "mineField")?;
+        let class = module.getattr("MineField")?;
+
+        let list = [
+            (Mode::Beginner, "BEGINNER_FIELD"),
+            (Mode::Intermediate, "INTERMEDIATE_FIELD"),
+            (Mode::Expert, "EXPERT_FIELD"),
+        ];
+
+        let presets = list
+            .into_iter()
+            .map(|(mode, name)| {
+                let kwargs = module
+                    .getattr(name)?
+                    .downcast::<PyDict>()
+                    .map_err(|e| anyhow!("{}", e))?;
+
+                let preset = Preset {
+                    width: PyAny::get_item(kwargs, "width")?.extract()?,
+                    height: PyAny::get_item(kwargs, "height")?.extract()?,
+                    mines: PyAny::get_item(kwargs, "number_of_mines")?.extract()?,
+                };
+
+                Ok((mode, (preset, kwargs)))
+            })
+            .collect::<Result<HashMap<Mode, (Preset, &PyDict)>>>()?;
+
+        Ok(Self { py, class, presets })
+    }
+
+    /// Board of a built-in mode as the module defines it, `None` for custom modes
+    pub fn preset(&self, mode: &Mode) -> Option<Preset> {
+        self.presets.get(mode).map(|(preset, _)| *preset)
+    }
+
+    /// Build a board, from the module's own constant when `preset` is one of the built-in modes
+    pub fn build(&self, preset: Preset) -> Result<PythonMinefield<'a>> {
+        let constant = self
+            .presets
+            .values()
+            .find(|(builtin, _)| *builtin == preset)
+            .map(|(_, kwargs)| *kwargs);
+        let kwargs = match constant {
+            Some(kwargs) => kwargs,
+            None => {
+                let kwargs = PyDict::new(self.py);
+                kwargs.set_item("width", preset.width)?;
+                kwargs.set_item("height", preset.height)?;
+                kwargs.set_item("number_of_mines", preset.mines)?;
+                kwargs
+            }
+        };
+        let field = self.class.call((), Some(kwargs))?;
+
+        Ok(PythonMinefield {
+            field,
+            width: preset.width,
+            height: preset.height,
+            number_of_mines: preset.mines,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct PythonMinefield<'a> {
+    field: &'a PyAny,
+    width: i32,
+    height: i32,
+    number_of_mines: i32,
+}
+
+impl<'a> PythonMinefield<'a> {
+    /// Describe an exception other than `ExplosionException` from `sweep_cell`. Python raises
+    /// `IndexError` for a cell off the board, and boards that check their arguments raise
+    /// `ValueError` for those or for a cell that was swept before. The solver shouldn't do
+    /// either, so these point at a solver bug.
+    fn sweep_error(&self, e: PyErr, column: i32, row: i32) -> anyhow::Error {
+        let py = self.field.py();
+        if !(e.is_instance_of::<PyIndexError>(py) || e.is_instance_of::<PyValueError>(py)) {
+            return anyhow::Error::from(e).context(format!(
+                "Python minefield failed to sweep ({},{})",
+                column, row
+            ));
+        }
+
+        if (0..self.width).contains(&column) && (0..self.height).contains(&row) {
+            anyhow!(
+                "Python minefield rejected ({},{}), was it swept before? {}",
+                column,
+                row,
+                e
+            )
+        } else {
+            anyhow!(
+                "Python minefield rejected ({},{}), outside the {}x{} board: {}",
+                column,
+                row,
+                self.width,
+                self.height,
+                e
+            )
+        }
+    }
+}
+
+impl<'a> Minefield for PythonMinefield<'a> {
+    fn sweep_cell(&mut self, column: i32, row: i32) -> Result<Cell> {
+        let result = self.field.call_method("sweep_cell", (column, row), None);
+        match result {
+            Ok(result) => Ok(Cell::Number(result.extract()?)),
+            Err(e) if format!("{}", e) == "ExplosionException: " => Ok(Cell::Mine),
+            Err(e) => Err(self.sweep_error(e, column, row)),
+        }
+    }
+
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn number_of_mines(&self) -> i32 {
+        self.number_of_mines
+    }
+}
+
+/// Solve a board from Python.
+///
+/// `board` needs a `sweep_cell(column, row)` method that returns the number of neighboring
+/// mines, or raises an `ExplosionException` for a mine, like `MineField` from the challenge.
+/// Returns the outcome (`"Won"`, `"Exploded"` or `"Incomplete"`) and the luck.
+#[pyfunction]
+fn solve(board: &PyAny, width: i32, height: i32, number_of_mines: i32) -> PyResult<(String, f32)> {
+    let mut minefield = PythonMinefield {
+        field: board,
+        width,
+        height,
+        number_of_mines,
+    };
+
+    let outcome = Solver::new(&mut minefield)
+        .and_then(|mut solver| solver.solve())
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+
+    Ok((outcome.status.name().to_string(), outcome.luck))
+}
+
+/// Python extension module, build with `maturin develop --features extension-module`
+#[pymodule]
+fn rusty_mines(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(solve, m)?)?;
+    Ok(())
+}
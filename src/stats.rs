@@ -0,0 +1,246 @@
+//! Aggregation of per-game values over big batches.
+//!
+//! The default `Aggregate::streaming` keeps memory constant however many games are played:
+//! mean and variance with Welford's algorithm, percentiles from a fixed size reservoir
+//! sample. `Aggregate::exact` stores every value instead, for exact percentiles.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Running mean and variance
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Welford {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            f64::NAN
+        } else {
+            self.mean
+        }
+    }
+
+    /// Sample variance
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            f64::NAN
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+}
+
+/// Uniform random sample of fixed size from a stream of unknown length
+pub struct Reservoir {
+    samples: Vec<f64>,
+    capacity: usize,
+    seen: u64,
+    rng: StdRng,
+}
+
+impl Reservoir {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            seen: 0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        self.seen += 1;
+        if self.samples.len() < self.capacity {
+            self.samples.push(value);
+        } else {
+            let i = self.rng.gen_range(0..self.seen);
+            if let Ok(i) = usize::try_from(i) {
+                if i < self.capacity {
+                    self.samples[i] = value;
+                }
+            }
+        }
+    }
+
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+}
+
+/// Nearest-rank percentile, `q` between 0 and 1
+pub fn percentile(values: &[f64], q: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Wilson score interval of a success rate, `z` is the normal quantile of the confidence
+/// level, e.g. 1.96 for 95%. Unlike the normal approximation it stays within 0 and 1 and works
+/// for rates close to them.
+pub fn wilson_interval(successes: u64, total: u64, z: f64) -> Option<(f64, f64)> {
+    if total == 0 {
+        return None;
+    }
+    let n = total as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let center = (p + z2 / (2f64 * n)) / (1f64 + z2 / n);
+    let margin = z / (1f64 + z2 / n) * (p * (1f64 - p) / n + z2 / (4f64 * n * n)).sqrt();
+    Some((center - margin, center + margin))
+}
+
+/// Counts of values between 0 and 1 in equal width bins, values outside go to the first or
+/// last bin
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            counts: vec![0; bins.max(1)],
+        }
+    }
+
+    pub fn push(&mut self, value: f64) {
+        let bins = self.counts.len();
+        let bin = (value * bins as f64).floor().clamp(0f64, (bins - 1) as f64) as usize;
+        self.counts[bin] += 1;
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Lower and upper end of bin `i`
+    pub fn bin_range(&self, i: usize) -> (f64, f64) {
+        let width = 1f64 / self.counts.len() as f64;
+        (i as f64 * width, (i + 1) as f64 * width)
+    }
+}
+
+pub struct Summary {
+    pub count: u64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub p10: Option<f64>,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+}
+
+pub enum Aggregate {
+    /// Boxed, the reservoir's RNG is big next to a `Vec`
+    Streaming(Welford, Box<Reservoir>),
+    Exact(Welford, Vec<f64>),
+}
+
+impl Aggregate {
+    /// Constant memory, percentiles are estimated from a sample
+    pub fn streaming() -> Self {
+        Self::Streaming(Welford::default(), Box::new(Reservoir::new(10_000)))
+    }
+
+    /// Keeps every value for exact percentiles
+    pub fn exact() -> Self {
+        Self::Exact(Welford::default(), Vec::new())
+    }
+
+    pub fn push(&mut self, value: f64) {
+        match self {
+            Self::Streaming(welford, reservoir) => {
+                welford.push(value);
+                reservoir.push(value);
+            }
+            Self::Exact(welford, values) => {
+                welford.push(value);
+                values.push(value);
+            }
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let (welford, values) = match self {
+            Self::Streaming(welford, reservoir) => (welford, reservoir.samples()),
+            Self::Exact(welford, values) => (welford, &values[..]),
+        };
+
+        Summary {
+            count: welford.count(),
+            mean: welford.mean(),
+            std_dev: welford.variance().sqrt(),
+            p10: percentile(values, 0.1),
+            p50: percentile(values, 0.5),
+            p90: percentile(values, 0.9),
+        }
+    }
+}
+
+#[test]
+fn streaming_matches_exact() {
+    let mut streaming = Aggregate::streaming();
+    let mut exact = Aggregate::exact();
+    for i in 1..=100 {
+        streaming.push(f64::from(i));
+        exact.push(f64::from(i));
+    }
+
+    let (streaming, exact) = (streaming.summary(), exact.summary());
+    assert_eq!(streaming.count, 100);
+    assert!((streaming.mean - 50.5).abs() < 1e-9);
+    assert!((streaming.std_dev - exact.std_dev).abs() < 1e-9);
+    // Everything fits in the reservoir, so the percentiles are exact too
+    assert_eq!(streaming.p50, Some(50f64));
+    assert_eq!(exact.p90, Some(90f64));
+}
+
+#[test]
+fn wilson() {
+    let (low, high) = wilson_interval(842, 1000, 1.96).unwrap();
+    assert!((low - 0.818).abs() < 0.001);
+    assert!((high - 0.863).abs() < 0.001);
+
+    // Stays within 0 and 1 at the edges
+    let (low, high) = wilson_interval(10, 10, 1.96).unwrap();
+    assert!(low > 0.7 && (high - 1f64).abs() < 1e-9);
+    assert_eq!(wilson_interval(0, 0, 1.96), None);
+}
+
+#[test]
+fn histogram() {
+    let mut histogram = Histogram::new(10);
+    for value in [0.0, 0.05, 0.15, 0.95, 1.0, 1.5] {
+        histogram.push(value);
+    }
+    assert_eq!(histogram.counts(), [2, 1, 0, 0, 0, 0, 0, 0, 0, 3]);
+    let (low, high) = histogram.bin_range(9);
+    assert!((low - 0.9).abs() < 1e-9 && (high - 1f64).abs() < 1e-9);
+}
+
+#[test]
+fn welford_big_batch() {
+    // A naive `f32` sum of these gives a mean of 0.290
+    let mut welford = Welford::default();
+    for _ in 0..5_000_000 {
+        welford.push(f64::from(0.3f32));
+    }
+    assert!((welford.mean() - f64::from(0.3f32)).abs() < 1e-12);
+}
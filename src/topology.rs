@@ -0,0 +1,90 @@
+//! Which cells count as neighbors, so the same solver works on other grids than squares.
+
+use crate::Pos;
+
+/// Neighbor layout of a grid. Neighbors may lie outside the board, callers skip those.
+pub trait Topology {
+    fn neighbors(&self, pos: Pos) -> Vec<Pos>;
+
+    /// Spaces in front of a row when showing the board
+    fn indent(&self, _row: i32) -> usize {
+        0
+    }
+}
+
+const SQUARE_NEIGHBORS: [(i32, i32); 8] = [
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, 1),
+    (0, -1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// The classic grid, 8 neighbors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Square;
+
+impl Topology for Square {
+    fn neighbors(&self, pos: Pos) -> Vec<Pos> {
+        let Pos(col, row) = pos;
+        SQUARE_NEIGHBORS
+            .iter()
+            .map(|(c, r)| Pos(col + c, row + r))
+            .collect()
+    }
+}
+
+/// Hexagons in offset coordinates, odd rows are shifted half a cell to the right. 6 neighbors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hex;
+
+impl Topology for Hex {
+    fn neighbors(&self, pos: Pos) -> Vec<Pos> {
+        let Pos(col, row) = pos;
+        // Column of the neighbors left and right of this cell, in the rows above and below
+        let (left, right) = if row % 2 == 0 {
+            (col - 1, col)
+        } else {
+            (col, col + 1)
+        };
+        vec![
+            Pos(col - 1, row),
+            Pos(col + 1, row),
+            Pos(left, row - 1),
+            Pos(right, row - 1),
+            Pos(left, row + 1),
+            Pos(right, row + 1),
+        ]
+    }
+
+    fn indent(&self, row: i32) -> usize {
+        (row % 2).try_into().unwrap()
+    }
+}
+
+#[test]
+fn hex_neighbors() {
+    let mut even = Hex.neighbors(Pos(1, 2));
+    even.sort();
+    assert_eq!(
+        even,
+        [
+            Pos(0, 1),
+            Pos(1, 1),
+            Pos(0, 2),
+            Pos(2, 2),
+            Pos(0, 3),
+            Pos(1, 3)
+        ]
+    );
+
+    // Neighbors are symmetric
+    for pos in [Pos(1, 2), Pos(2, 3)] {
+        for neighbor in Hex.neighbors(pos) {
+            assert!(Hex.neighbors(neighbor).contains(&pos));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Full screen view of a single game, stepping through the solve a key press at a time. Only
+//! the cells that a step changed get redrawn.
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    execute, queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use owo_colors::OwoColorize;
+use rusty_mines::{topology::Topology, Cell, Minefield, Pos, RenderStyle, Solver, Status};
+use std::io::{self, Write};
+
+/// Raw mode on the alternate screen, restores the terminal when dropped, also on errors
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn glyph(glyphs: &RenderStyle, cell: Cell) -> String {
+    let plain = glyphs.glyph(cell);
+    match cell {
+        Cell::Flag => plain.bold().cyan().to_string(),
+        Cell::Mine => plain.bold().red().to_string(),
+        _ => plain,
+    }
+}
+
+/// Column and row on the screen, laid out like `Solver::render`
+fn screen_pos(
+    topology: &dyn Topology,
+    glyphs: &RenderStyle,
+    Pos(col, row): Pos,
+) -> Result<(u16, u16)> {
+    let indent = topology.indent(row);
+    let x = usize::try_from(col)? * (glyphs.cell_width() + 1) + indent;
+    Ok((u16::try_from(x)?, u16::try_from(row)?))
+}
+
+fn draw_status<W: Write, M: Minefield>(w: &mut W, solver: &Solver<'_, M>) -> Result<()> {
+    let info = solver.info();
+    let state = match solver.outcome() {
+        Some(outcome) => format!("{:?}, q quits", outcome.status),
+        None => "space or → steps, end solves, q quits".to_string(),
+    };
+    let row = u16::try_from(info.height + 1)?;
+    queue!(
+        w,
+        cursor::MoveTo(0, row),
+        terminal::Clear(ClearType::CurrentLine),
+        Print(format!(
+            "Flags {}, mines remaining {}, luck {:.4} | {}",
+            info.flags_placed,
+            info.mines_remaining,
+            solver.luck(),
+            state
+        ))
+    )?;
+    Ok(())
+}
+
+/// Step through a game from where the solver is, returns `None` when quit before the game is over
+pub fn run<M: Minefield>(
+    topology: &dyn Topology,
+    glyphs: &RenderStyle,
+    mut solver: Solver<'_, M>,
+) -> Result<Option<Status>> {
+    let screen = Screen::enter()?;
+    let mut out = io::stdout();
+
+    queue!(out, terminal::Clear(ClearType::All))?;
+    for pos in solver.positions() {
+        let (x, y) = screen_pos(topology, glyphs, pos)?;
+        let cell = solver.get(pos).unwrap_or(Cell::Unknown);
+        queue!(out, cursor::MoveTo(x, y), Print(glyph(glyphs, cell)))?;
+    }
+    draw_status(&mut out, &solver)?;
+    out.flush()?;
+
+    loop {
+        let steps = match event::read()? {
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) => match code {
+                KeyCode::Char(' ') | KeyCode::Right => 1,
+                KeyCode::End => usize::MAX,
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                _ => 0,
+            },
+            _ => 0,
+        };
+
+        for _ in 0..steps {
+            if solver.outcome().is_some() {
+                break;
+            }
+            for (pos, cell) in solver.step()? {
+                let (x, y) = screen_pos(topology, glyphs, pos)?;
+                queue!(out, cursor::MoveTo(x, y), Print(glyph(glyphs, cell)))?;
+            }
+        }
+        draw_status(&mut out, &solver)?;
+        out.flush()?;
+    }
+
+    drop(screen);
+    Ok(solver.outcome().map(|outcome| outcome.status))
+}
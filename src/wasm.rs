@@ -0,0 +1,31 @@
+//! Browser bindings, build with `wasm-pack build --target web`. Only the native minefield is
+//! available there.
+
+use crate::{Move, Outcome, Preset, RustMinefield, Solver};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// What `solve_board` returns to JavaScript
+#[derive(Serialize)]
+struct SolvedBoard {
+    outcome: Outcome,
+    /// Every move of the solver, to replay the game on a page
+    moves: Vec<Move>,
+}
+
+/// Solve a seeded native board, returns `{ outcome, moves }` or throws the error message
+#[wasm_bindgen]
+pub fn solve_board(width: i32, height: i32, mines: i32, seed: u64) -> Result<JsValue, JsValue> {
+    let solve = || -> anyhow::Result<SolvedBoard> {
+        let mut minefield = RustMinefield::with_seed(Preset::new(width, height, mines)?, seed);
+        let mut solver = Solver::new(&mut minefield)?;
+        let outcome = solver.solve()?;
+        Ok(SolvedBoard {
+            outcome,
+            moves: solver.moves().to_vec(),
+        })
+    };
+
+    let solved = solve().map_err(|e| JsValue::from_str(&e.to_string()))?;
+    serde_wasm_bindgen::to_value(&solved).map_err(JsValue::from)
+}